@@ -0,0 +1,434 @@
+use anyhow::{anyhow, Result};
+use kubewarden::host_capabilities::oci::OciManifestResponse;
+use std::collections::BTreeMap;
+use std::fmt;
+use x509_parser::der_parser::der::parse_der_utf8string;
+use x509_parser::prelude::*;
+
+#[cfg(test)]
+use crate::tests::mock_oci_sdk::{get_manifest, get_manifest_digest};
+#[cfg(not(test))]
+use kubewarden::host_capabilities::oci::{get_manifest, get_manifest_digest};
+
+/// Annotation of the layers of the cosign signature manifest holding the
+/// certificate of keyless signatures
+const CERTIFICATE_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
+
+/// Prefix of the OIDs of the Fulcio certificate extensions
+pub(crate) const FULCIO_OID_PREFIX: &str = "1.3.6.1.4.1.57264.1.";
+
+/// Fulcio extension with the OIDC issuer, stored as raw bytes
+const OIDC_ISSUER_OID: &str = "1.3.6.1.4.1.57264.1.1";
+/// Fulcio extension with the event that triggered the GitHub workflow,
+/// stored as raw bytes
+pub(crate) const GITHUB_WORKFLOW_TRIGGER_OID: &str = "1.3.6.1.4.1.57264.1.2";
+/// Fulcio extension with the OIDC issuer, stored as a DER UTF8String
+const OIDC_ISSUER_V2_OID: &str = "1.3.6.1.4.1.57264.1.8";
+/// Fulcio extension with the kind of runner of the build, e.g. `github-hosted`
+pub(crate) const RUNNER_ENVIRONMENT_OID: &str = "1.3.6.1.4.1.57264.1.11";
+/// Fulcio extension with the event that triggered the build
+pub(crate) const BUILD_TRIGGER_OID: &str = "1.3.6.1.4.1.57264.1.20";
+
+/// Identity of the signer found in the certificate of a keyless signature
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CertificateIdentity {
+    pub(crate) issuer: String,
+    pub(crate) subject: String,
+    /// Fulcio extensions of the certificate, by OID
+    pub(crate) extensions: BTreeMap<String, String>,
+}
+
+impl CertificateIdentity {
+    /// Returns the value of the first of the given extensions found in the
+    /// certificate
+    pub(crate) fn extension(&self, oids: &[&str]) -> Option<&str> {
+        oids.iter()
+            .find_map(|oid| self.extensions.get(*oid))
+            .map(String::as_str)
+    }
+
+    /// Returns true when the signer is the same, regardless of the extensions
+    pub(crate) fn same_signer(&self, other: &CertificateIdentity) -> bool {
+        self.issuer == other.issuer && self.subject == other.subject
+    }
+}
+
+impl fmt::Display for CertificateIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.subject, self.issuer)
+    }
+}
+
+/// Certificates attached to the cosign signatures of an image
+pub(crate) struct SignatureCertificates {
+    /// Reference of the image pinned to the digest whose signatures have been
+    /// read, e.g. `ghcr.io/acme/app@sha256:<hex>`. The candidates must be
+    /// verified against it: the tag may have moved since the signatures have
+    /// been read.
+    pub(crate) image: String,
+    /// PEM encoded certificates, without duplicates
+    pub(crate) certificates: Vec<String>,
+}
+
+/// Identities of the certificates attached to the cosign signatures of an
+/// image
+pub(crate) struct SignatureIdentities {
+    /// Reference of the image pinned to the digest whose signatures have been
+    /// read, see [`SignatureCertificates::image`]
+    pub(crate) image: String,
+    pub(crate) identities: Vec<CertificateIdentity>,
+    /// Number of certificates whose identity cannot be read
+    pub(crate) unparsable: usize,
+}
+
+impl fmt::Display for SignatureIdentities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "identities found: [{}]",
+            self.identities
+                .iter()
+                .map(|identity| identity.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+        if self.unparsable > 0 {
+            write!(
+                f,
+                "; certificates that cannot be parsed: {}",
+                self.unparsable
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the identities of the certificates attached to the cosign
+/// signatures of the image.
+///
+/// The identities are not verified: they are only used to find the
+/// candidates that are then verified by the host.
+pub(crate) fn signature_identities(image: &str) -> Result<SignatureIdentities> {
+    let SignatureCertificates {
+        image,
+        certificates,
+    } = signature_certificates(image)?;
+    let identities: Vec<CertificateIdentity> = certificates
+        .iter()
+        .filter_map(|certificate| certificate_identity(certificate.as_bytes()))
+        .collect();
+
+    Ok(SignatureIdentities {
+        image,
+        unparsable: certificates.len() - identities.len(),
+        identities,
+    })
+}
+
+/// Returns the PEM encoded certificates attached to the cosign signatures of
+/// the image. Like the identities, they are not verified.
+///
+/// Only the signatures stored in the `sha256-<hex>.sig` tag are read: the
+/// signatures attached as cosign bundles or OCI referrers are not seen.
+pub(crate) fn signature_certificates(image: &str) -> Result<SignatureCertificates> {
+    let digest = get_manifest_digest(image)
+        .map_err(|e| anyhow!("cannot get the digest of {image}: {e}"))?
+        .digest;
+    let signature_image = signature_image(image, &digest);
+
+    let manifest = match get_manifest(&signature_image)
+        .map_err(|e| anyhow!("cannot get the signatures of {image}: {e}"))?
+    {
+        OciManifestResponse::Image(manifest) => manifest,
+        OciManifestResponse::ImageIndex(_) => {
+            return Err(anyhow!("{signature_image} is not a signature manifest"))
+        }
+    };
+
+    let mut certificates: Vec<String> = vec![];
+    for certificate in manifest
+        .layers()
+        .iter()
+        .filter_map(|layer| layer.annotations().as_ref()?.get(CERTIFICATE_ANNOTATION))
+    {
+        if !certificates.contains(certificate) {
+            certificates.push(certificate.clone());
+        }
+    }
+    Ok(SignatureCertificates {
+        image: format!("{}@{digest}", image_repository(image)),
+        certificates,
+    })
+}
+
+/// Reference of the cosign signature manifest of the image with the given
+/// digest, e.g. `ghcr.io/acme/app:sha256-<hex>.sig`
+fn signature_image(image: &str, digest: &str) -> String {
+    format!(
+        "{}:{}.sig",
+        image_repository(image),
+        digest.replace(':', "-")
+    )
+}
+
+/// Repository of the image, without its tag and its digest
+fn image_repository(image: &str) -> String {
+    let repository = image.split('@').next().unwrap_or(image);
+    match repository.rsplit_once('/') {
+        Some((path, name)) => format!("{path}/{}", name.split(':').next().unwrap_or(name)),
+        None => repository
+            .split(':')
+            .next()
+            .unwrap_or(repository)
+            .to_string(),
+    }
+}
+
+/// Extracts the issuer, the subject and the Fulcio extensions from a PEM
+/// encoded Fulcio certificate
+pub(crate) fn certificate_identity(pem: &[u8]) -> Option<CertificateIdentity> {
+    let (_, pem) = parse_x509_pem(pem).ok()?;
+    let certificate = pem.parse_x509().ok()?;
+
+    let extensions: BTreeMap<String, String> = certificate
+        .extensions()
+        .iter()
+        .filter_map(|extension| {
+            let oid = extension.oid.to_id_string();
+            if !oid.starts_with(FULCIO_OID_PREFIX) {
+                return None;
+            }
+            extension_value(extension.value).map(|value| (oid, value))
+        })
+        .collect();
+
+    let issuer = [OIDC_ISSUER_V2_OID, OIDC_ISSUER_OID]
+        .iter()
+        .find_map(|oid| extensions.get(*oid))?
+        .clone();
+
+    let subject = certificate
+        .subject_alternative_name()
+        .ok()??
+        .value
+        .general_names
+        .iter()
+        .find_map(|name| match name {
+            GeneralName::RFC822Name(email) => Some(email.to_string()),
+            GeneralName::URI(uri) => Some(uri.to_string()),
+            _ => None,
+        })?;
+
+    Some(CertificateIdentity {
+        issuer,
+        subject,
+        extensions,
+    })
+}
+
+/// Decodes the value of a Fulcio extension: the recent extensions are DER
+/// UTF8Strings, while the deprecated ones are raw strings
+fn extension_value(value: &[u8]) -> Option<String> {
+    match parse_der_utf8string(value) {
+        Ok((&[], value)) => value.as_str().ok().map(String::from),
+        _ => std::str::from_utf8(value).ok().map(String::from),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::tests::mock_oci_sdk;
+    use kubewarden::host_capabilities::oci::ManifestDigestResponse;
+    use serial_test::serial;
+
+    pub(crate) const RELEASE_CERTIFICATE: &str = include_str!("../test_data/keyless/release.pem");
+    pub(crate) const DEVELOPER_CERTIFICATE: &str =
+        include_str!("../test_data/keyless/developer.pem");
+    pub(crate) const PULL_REQUEST_CERTIFICATE: &str =
+        include_str!("../test_data/keyless/pull-request.pem");
+    pub(crate) const GITLAB_CERTIFICATE: &str = include_str!("../test_data/keyless/gitlab.pem");
+
+    pub(crate) const IMAGE_DIGEST: &str =
+        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e";
+
+    /// Cosign signature manifest with one layer for each certificate
+    pub(crate) fn signature_manifest(certificates: &[&str]) -> OciManifestResponse {
+        let layers: Vec<serde_json::Value> = certificates
+            .iter()
+            .map(|certificate| {
+                serde_json::json!({
+                    "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+                    "digest": IMAGE_DIGEST,
+                    "size": 250,
+                    "annotations": {
+                        "dev.cosignproject.cosign/signature": "signature",
+                        CERTIFICATE_ANNOTATION: certificate
+                    }
+                })
+            })
+            .collect();
+
+        serde_json::from_value(serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.oci.image.config.v1+json",
+                "digest": IMAGE_DIGEST,
+                "size": 233
+            },
+            "layers": layers
+        }))
+        .expect("cannot build the signature manifest")
+    }
+
+    /// Mocks the OCI host capabilities to return a signature manifest with
+    /// the given certificates
+    pub(crate) fn mock_signatures(
+        certificates: &'static [&'static str],
+    ) -> (
+        mock_oci_sdk::__get_manifest_digest::Context,
+        mock_oci_sdk::__get_manifest::Context,
+    ) {
+        let ctx_digest = mock_oci_sdk::get_manifest_digest_context();
+        ctx_digest.expect().returning(|_| {
+            Ok(ManifestDigestResponse {
+                digest: IMAGE_DIGEST.to_string(),
+            })
+        });
+
+        let ctx_manifest = mock_oci_sdk::get_manifest_context();
+        ctx_manifest
+            .expect()
+            .returning(move |_| Ok(signature_manifest(certificates)));
+
+        (ctx_digest, ctx_manifest)
+    }
+
+    #[test]
+    fn signature_image_reference() {
+        let digest = "sha256:abc";
+        assert_eq!(
+            signature_image("ghcr.io/acme/app:v1", digest),
+            "ghcr.io/acme/app:sha256-abc.sig"
+        );
+        assert_eq!(
+            signature_image("localhost:5000/app@sha256:abc", digest),
+            "localhost:5000/app:sha256-abc.sig"
+        );
+        assert_eq!(signature_image("nginx", digest), "nginx:sha256-abc.sig");
+    }
+
+    #[test]
+    fn repository_of_the_image() {
+        assert_eq!(image_repository("ghcr.io/acme/app:v1"), "ghcr.io/acme/app");
+        assert_eq!(
+            image_repository("localhost:5000/app:v1@sha256:abc"),
+            "localhost:5000/app"
+        );
+        assert_eq!(image_repository("nginx"), "nginx");
+    }
+
+    #[test]
+    fn identity_from_certificate() {
+        let identity = certificate_identity(RELEASE_CERTIFICATE.as_bytes()).unwrap();
+        assert_eq!(
+            identity.issuer,
+            "https://token.actions.githubusercontent.com"
+        );
+        assert_eq!(
+            identity.subject,
+            "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+        );
+        assert_eq!(
+            identity.extension(&[BUILD_TRIGGER_OID, GITHUB_WORKFLOW_TRIGGER_OID]),
+            Some("push")
+        );
+        assert_eq!(
+            identity.extension(&[RUNNER_ENVIRONMENT_OID]),
+            Some("github-hosted")
+        );
+
+        let identity = certificate_identity(DEVELOPER_CERTIFICATE.as_bytes()).unwrap();
+        assert_eq!(identity.issuer, "https://accounts.google.com");
+        assert_eq!(identity.subject, "alice@acme.org");
+
+        assert!(certificate_identity(b"not a certificate").is_none());
+    }
+
+    #[test]
+    fn identity_from_certificate_with_deprecated_extensions() {
+        let identity = certificate_identity(PULL_REQUEST_CERTIFICATE.as_bytes()).unwrap();
+        assert_eq!(
+            identity.issuer,
+            "https://token.actions.githubusercontent.com"
+        );
+        assert_eq!(
+            identity.extension(&[BUILD_TRIGGER_OID, GITHUB_WORKFLOW_TRIGGER_OID]),
+            Some("pull_request")
+        );
+        assert_eq!(
+            identity.extension(&[RUNNER_ENVIRONMENT_OID]),
+            Some("self-hosted")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn identities_of_the_signatures() {
+        let ctx_manifest = mock_oci_sdk::get_manifest_context();
+        ctx_manifest
+            .expect()
+            .times(1)
+            .withf(|image| {
+                image == "ghcr.io/acme/app:sha256-89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e.sig"
+            })
+            .returning(|_| Ok(signature_manifest(&[RELEASE_CERTIFICATE, DEVELOPER_CERTIFICATE])));
+        let ctx_digest = mock_oci_sdk::get_manifest_digest_context();
+        ctx_digest.expect().times(1).returning(|_| {
+            Ok(ManifestDigestResponse {
+                digest: IMAGE_DIGEST.to_string(),
+            })
+        });
+
+        let identities = signature_identities("ghcr.io/acme/app:v1.2.0").unwrap();
+        assert_eq!(identities.image, format!("ghcr.io/acme/app@{IMAGE_DIGEST}"));
+        assert_eq!(identities.identities.len(), 2);
+        assert_eq!(identities.identities[1].subject, "alice@acme.org");
+        assert_eq!(identities.unparsable, 0);
+    }
+
+    #[test]
+    #[serial]
+    fn unparsable_certificates_are_reported() {
+        let _ctx_oci = mock_signatures(&[RELEASE_CERTIFICATE, "not a certificate"]);
+
+        let identities = signature_identities("ghcr.io/acme/app:v1.2.0").unwrap();
+        assert_eq!(identities.identities.len(), 1);
+        assert_eq!(identities.unparsable, 1);
+        assert_eq!(
+            identities.to_string(),
+            "identities found: [https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0 (https://token.actions.githubusercontent.com)]; certificates that cannot be parsed: 1"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn certificates_of_the_signatures() {
+        let _ctx_oci = mock_signatures(&[
+            RELEASE_CERTIFICATE,
+            DEVELOPER_CERTIFICATE,
+            RELEASE_CERTIFICATE,
+        ]);
+
+        let certificates = signature_certificates("ghcr.io/acme/app:v1.2.0").unwrap();
+        assert_eq!(
+            certificates.image,
+            format!("ghcr.io/acme/app@{IMAGE_DIGEST}")
+        );
+        assert_eq!(
+            certificates.certificates,
+            vec![RELEASE_CERTIFICATE, DEVELOPER_CERTIFICATE]
+        );
+    }
+}
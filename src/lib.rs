@@ -2,34 +2,63 @@ use guest::prelude::*;
 use k8s_openapi::api::{
     apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     batch::v1::{CronJob, Job},
-    core::v1::{Container, EphemeralContainer, Pod, PodSpec, ReplicationController},
+    core::v1::{Container, EphemeralContainer, Namespace, Pod, PodSpec, ReplicationController},
 };
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kubewarden_policy_sdk::wapc_guest as guest;
 use lazy_static::lazy_static;
 use serde::Serialize;
 
 extern crate kubewarden_policy_sdk as kubewarden;
 #[cfg(test)]
+use crate::tests::mock_kubernetes_sdk::get_resource;
+#[cfg(test)]
 use crate::tests::mock_verification_sdk::{
     verify_certificate, verify_keyless_exact_match, verify_keyless_github_actions,
     verify_keyless_prefix_match, verify_pub_keys_image,
 };
 use anyhow::Result;
-use kubewarden::host_capabilities::verification::VerificationResponse;
+use chrono::{DateTime, Utc};
+#[cfg(not(test))]
+use kubewarden::host_capabilities::kubernetes::get_resource;
 #[cfg(not(test))]
 use kubewarden::host_capabilities::verification::{
     verify_certificate, verify_keyless_exact_match, verify_keyless_github_actions,
     verify_keyless_prefix_match, verify_pub_keys_image,
 };
-use kubewarden::{logging, protocol_version_guest, request::ValidationRequest, validate_settings};
+use kubewarden::host_capabilities::{
+    kubernetes::GetResourceRequest,
+    verification::{KeylessInfo, KeylessPrefixInfo, VerificationResponse},
+};
+use kubewarden::{
+    logging, protocol_version_guest,
+    request::{KubernetesAdmissionRequest, ValidationRequest},
+    response::ValidationResponse,
+    validate_settings,
+};
 use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
+
+mod cosign;
+use cosign::{
+    signature_certificates, signature_identities, CertificateIdentity, SignatureIdentities,
+};
 
 mod settings;
 use settings::Settings;
 
-use crate::settings::Signature;
+use crate::settings::{
+    annotations::TemplateContext,
+    certificate_extensions::CertificateExtensions,
+    crl::{revocation, Revocation},
+    group::GroupRule,
+    is_short_name,
+    leaf_certificate::certificate_subject,
+    pem_material::{active_pems, signer_identity},
+    Certificate, GithubActions, GitlabCi, Keyless, KeylessMatch, KeylessPrefix, LeafCertificate,
+    Signature, SignatureGroup, UnmatchedImages,
+};
 use slog::{o, warn, Logger};
-use wildmatch::WildMatch;
 
 lazy_static! {
     static ref LOG_DRAIN: Logger = Logger::root(
@@ -207,7 +236,24 @@ impl ValidatingResource for CronJob {
 }
 
 fn validate(payload: &[u8]) -> CallResult {
-    let validation_request: ValidationRequest<Settings> = ValidationRequest::new(payload)?;
+    let mut validation_request: ValidationRequest<Settings> = ValidationRequest::new(payload)?;
+
+    // Requesters are evaluated before the resource, so that exempted requests
+    // never reach the host verification functions
+    let requesters = std::mem::take(&mut validation_request.settings.requesters);
+    let user_info = &validation_request.request.user_info;
+    if let Some(requester) = requesters.into_iter().find(|r| r.matches(user_info)) {
+        match requester.signatures {
+            Some(signatures) => validation_request.settings.signatures = signatures,
+            None => {
+                let exemption = format!(
+                    "request from {} exempted: {}",
+                    user_info.username, requester.reason
+                );
+                return accept_request(None, "exempted-requester", vec![exemption], vec![]);
+            }
+        }
+    }
 
     match validation_request.request.kind.kind.as_str() {
         "Deployment" => validate_resource::<Deployment>(validation_request),
@@ -230,8 +276,13 @@ fn validate(payload: &[u8]) -> CallResult {
 // validate any resource that contains a Pod. e.g. Deployment, StatefulSet, ...
 // it does not modify the container with the manifest digest.
 fn validate_resource<T: ValidatingResource + DeserializeOwned + Serialize>(
-    validation_request: ValidationRequest<Settings>,
+    mut validation_request: ValidationRequest<Settings>,
 ) -> CallResult {
+    let namespace = validation_request.request.namespace.as_str();
+    if validation_request.settings.is_namespace_excluded(namespace) {
+        return kubewarden::accept_request();
+    }
+
     let resource = match serde_json::from_value::<T>(validation_request.request.object.clone()) {
         Ok(resource) => resource,
         Err(_) => {
@@ -249,14 +300,71 @@ fn validate_resource<T: ValidatingResource + DeserializeOwned + Serialize>(
         }
     };
 
-    let changed_spec =
-        match verify_all_images_in_pod(&spec, &validation_request.settings.signatures) {
-            Ok(spec) => match spec {
-                Some(spec) => spec,
-                None => {
-                    return kubewarden::accept_request();
-                }
-            },
+    let namespace = match request_namespace(
+        &validation_request.request.namespace,
+        &validation_request.settings,
+    ) {
+        Ok(namespace) => namespace,
+        Err(error) => {
+            return kubewarden::reject_request(
+                Some(format!(
+                    "Resource {} is not accepted: cannot get namespace {}: {}",
+                    &resource.name(),
+                    &validation_request.request.namespace,
+                    error
+                )),
+                None,
+                None,
+                None,
+            );
+        }
+    };
+
+    let images = pod_images(&spec);
+    if let Err(error) = validation_request
+        .settings
+        .resolve_pem_references(|signature| {
+            images
+                .iter()
+                .any(|image| signature_applies(signature, image, &namespace))
+        })
+    {
+        return kubewarden::reject_request(
+            Some(format!(
+                "Resource {} is not accepted: cannot load the keys of {}",
+                &resource.name(),
+                error
+            )),
+            None,
+            None,
+            None,
+        );
+    }
+
+    let name = template_name(&validation_request.request);
+    let template_context = TemplateContext {
+        name: &name,
+        namespace: &namespace,
+    };
+    if let Err(error) = validation_request
+        .settings
+        .resolve_annotation_templates(&template_context)
+    {
+        return kubewarden::reject_request(
+            Some(format!(
+                "Resource {} is not accepted: cannot resolve the annotations of {}",
+                &resource.name(),
+                error
+            )),
+            None,
+            None,
+            None,
+        );
+    }
+
+    let verified_spec =
+        match verify_all_images_in_pod(&spec, &validation_request.settings, &namespace) {
+            Ok(verified_spec) => verified_spec,
             Err(error) => {
                 return kubewarden::reject_request(
                     Some(format!(
@@ -271,38 +379,161 @@ fn validate_resource<T: ValidatingResource + DeserializeOwned + Serialize>(
             }
         };
 
-    if !validation_request.settings.modify_images_with_digest {
-        return kubewarden::accept_request();
-    }
+    let changed_spec = match verified_spec.spec {
+        Some(spec) if validation_request.settings.modify_images_with_digest => spec,
+        _ => {
+            return accept_request(
+                None,
+                "exempted-images",
+                verified_spec.exemptions,
+                verified_spec.warnings,
+            )
+        }
+    };
 
     let mut resource = resource;
     resource.set_spec(changed_spec);
 
     let mutated_object = serde_json::to_value(&resource)?;
-    kubewarden::mutate_request(mutated_object)
+    accept_request(
+        Some(mutated_object),
+        "exempted-images",
+        verified_spec.exemptions,
+        verified_spec.warnings,
+    )
+}
+
+/// Returns the name of the admitted resource used by the annotation templates.
+/// The name of the resources created through `generateName`, like the Pods of
+/// a ReplicaSet, is set after the admission: their `generateName` prefix is
+/// used instead.
+fn template_name(request: &KubernetesAdmissionRequest) -> String {
+    if !request.name.is_empty() {
+        return request.name.clone();
+    }
+    request
+        .object
+        .pointer("/metadata/generateName")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Returns the images of all the containers of the PodSpec
+fn pod_images(spec: &PodSpec) -> Vec<String> {
+    spec.containers
+        .iter()
+        .filter_map(ImageHolder::get_image)
+        .chain(
+            spec.init_containers
+                .iter()
+                .flatten()
+                .filter_map(ImageHolder::get_image),
+        )
+        .chain(
+            spec.ephemeral_containers
+                .iter()
+                .flatten()
+                .filter_map(ImageHolder::get_image),
+        )
+        .collect()
+}
+
+/// Returns the namespace of the request. Its labels and annotations are
+/// fetched from the cluster only when they are needed to select the signature
+/// rules or to resolve the annotation templates.
+fn request_namespace(name: &str, settings: &Settings) -> Result<Namespace> {
+    if name.is_empty()
+        || !(settings.uses_namespace_selectors() || settings.uses_namespace_templates())
+    {
+        return Ok(Namespace {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+    }
+
+    get_resource(&GetResourceRequest {
+        api_version: "v1".to_string(),
+        kind: "Namespace".to_string(),
+        name: name.to_string(),
+        namespace: None,
+        disable_cache: false,
+    })
+}
+
+/// Accept the request, reporting the exemptions that let it in through
+/// warnings and the given audit annotation
+fn accept_request(
+    mutated_object: Option<serde_json::Value>,
+    audit_annotation: &str,
+    exemptions: Vec<String>,
+    warnings: Vec<String>,
+) -> CallResult {
+    if exemptions.is_empty() && warnings.is_empty() {
+        return match mutated_object {
+            Some(mutated_object) => kubewarden::mutate_request(mutated_object),
+            None => kubewarden::accept_request(),
+        };
+    }
+
+    let audit_annotations = (!exemptions.is_empty())
+        .then(|| HashMap::from([(audit_annotation.to_string(), exemptions.join("; "))]));
+    Ok(serde_json::to_vec(&ValidationResponse {
+        accepted: true,
+        message: None,
+        code: None,
+        mutated_object,
+        audit_annotations,
+        warnings: Some([exemptions, warnings].concat()),
+    })?)
+}
+
+/// Result of the verification of the images of a PodSpec
+struct VerifiedPodSpec {
+    /// PodSpec with the images replaced with the digest which was used for the
+    /// verification, `None` when no image has been changed
+    spec: Option<PodSpec>,
+    /// Exemptions that let images in without verification
+    exemptions: Vec<String>,
+    /// Warnings about the images that have been let in
+    warnings: Vec<String>,
 }
 
 /// verify all images and return a PodSpec with the images replaced with the digest which was used for the verification
 fn verify_all_images_in_pod(
     spec: &PodSpec,
-    signatures: &[Signature],
-) -> Result<Option<PodSpec>, String> {
+    settings: &Settings,
+    namespace: &Namespace,
+) -> Result<VerifiedPodSpec, String> {
     let mut policy_verification_errors: Vec<String> = vec![];
+    let mut exemptions: Vec<String> = vec![];
+    let mut unmatched_images: Vec<String> = vec![];
     let mut spec_images_with_digest = spec.clone();
     let mut is_modified_with_digest = false;
 
     if let Some(containers_with_digest) = verify_container_images(
         &spec.containers,
         &mut policy_verification_errors,
-        signatures,
+        &mut exemptions,
+        &mut unmatched_images,
+        settings,
+        namespace,
     ) {
         spec_images_with_digest.containers = containers_with_digest;
         is_modified_with_digest = true;
     }
     if let Some(init_containers) = &spec.init_containers {
-        if let Some(init_containers_with_digest) =
-            verify_container_images(init_containers, &mut policy_verification_errors, signatures)
-        {
+        if let Some(init_containers_with_digest) = verify_container_images(
+            init_containers,
+            &mut policy_verification_errors,
+            &mut exemptions,
+            &mut unmatched_images,
+            settings,
+            namespace,
+        ) {
             spec_images_with_digest.init_containers = Some(init_containers_with_digest);
             is_modified_with_digest = true;
         }
@@ -311,29 +542,48 @@ fn verify_all_images_in_pod(
         if let Some(ephemeral_containers_with_digest) = verify_container_images(
             ephemeral_containers,
             &mut policy_verification_errors,
-            signatures,
+            &mut exemptions,
+            &mut unmatched_images,
+            settings,
+            namespace,
         ) {
             spec_images_with_digest.ephemeral_containers = Some(ephemeral_containers_with_digest);
             is_modified_with_digest = true;
         }
     }
 
+    let mut warnings: Vec<String> = vec![];
+    if !unmatched_images.is_empty() {
+        let unmatched = format!(
+            "no signature rule matches the images: [{}]",
+            unmatched_images.join(", ")
+        );
+        match settings.unmatched_images {
+            UnmatchedImages::Allow => {}
+            UnmatchedImages::Warn => warnings.push(unmatched),
+            UnmatchedImages::Deny => policy_verification_errors.push(unmatched),
+        }
+    }
+
     if !policy_verification_errors.is_empty() {
         return Err(policy_verification_errors.join(", "));
     }
 
-    if is_modified_with_digest {
-        Ok(Some(spec_images_with_digest))
-    } else {
-        Ok(None)
-    }
+    Ok(VerifiedPodSpec {
+        spec: is_modified_with_digest.then_some(spec_images_with_digest),
+        exemptions,
+        warnings,
+    })
 }
 
 // verify images and return containers with the images replaced with the digest which was used for the verification
 fn verify_container_images<T>(
     containers: &[T],
     policy_verification_errors: &mut Vec<String>,
-    signatures: &[Signature],
+    exemptions: &mut Vec<String>,
+    unmatched_images: &mut Vec<String>,
+    settings: &Settings,
+    namespace: &Namespace,
 ) -> Option<Vec<T>>
 where
     T: ImageHolder + PartialEq,
@@ -343,45 +593,353 @@ where
     for (i, container) in containers.iter().enumerate() {
         let container_image = container.get_image().unwrap();
 
-        for signature in signatures.iter() {
+        if settings.reject_short_names && is_short_name(&container_image) {
+            policy_verification_errors.push(format!(
+                "image {container_image} does not name its registry, use a fully qualified image"
+            ));
+            continue;
+        }
+
+        if let Some(skip_image) = settings.skipped_image(&container_image) {
+            exemptions.push(format!(
+                "image {container_image} exempted by skipped image {}: {}",
+                skip_image.image, skip_image.reason
+            ));
+            continue;
+        }
+
+        let mut is_matched = false;
+        for signature in settings.signatures.iter() {
             // verify if the name matches the image name provided
-            if !WildMatch::new(signature.image()).matches(container_image.as_str()) {
+            if !signature_applies(signature, container_image.as_str(), namespace) {
                 continue;
             }
+            is_matched = true;
 
-            let verification_response = match signature {
-                Signature::PubKeys(s) => verify_pub_keys_image(
-                    container_image.as_str(),
-                    s.pub_keys.clone(),
-                    s.annotations.clone(),
-                ),
-                Signature::Keyless(s) => verify_keyless_exact_match(
-                    container_image.as_str(),
-                    s.keyless.clone(),
-                    s.annotations.clone(),
-                ),
-                Signature::KeylessPrefix(s) => verify_keyless_prefix_match(
-                    container_image.as_str(),
-                    s.keyless_prefix.clone(),
-                    s.annotations.clone(),
+            let verification_response =
+                verify_signature(signature, container_image.as_str(), namespace);
+
+            handle_verification_response(
+                verification_response,
+                container_image.as_str(),
+                &mut container_with_images_digests[i],
+                policy_verification_errors,
+            );
+        }
+
+        if !is_matched {
+            unmatched_images.push(container_image);
+        }
+    }
+
+    if containers != container_with_images_digests {
+        Some(container_with_images_digests.to_vec())
+    } else {
+        None
+    }
+}
+
+fn signature_applies(signature: &Signature, container_image: &str, namespace: &Namespace) -> bool {
+    signature.scope().validity.is_active(Utc::now())
+        && signature.applies_to_namespace(namespace)
+        && signature.matches_image(container_image)
+        && !signature.excludes_image(container_image)
+}
+
+/// Verify the image against a single signature rule. The rule is satisfied
+/// when the image has been signed with one of the combinations of the
+/// required annotations, and none of the signatures has a forbidden one.
+fn verify_signature(
+    signature: &Signature,
+    container_image: &str,
+    namespace: &Namespace,
+) -> Result<VerificationResponse> {
+    let Some(annotations) = &signature.scope().annotations else {
+        return verify_signature_with_annotations(signature, container_image, namespace, &None);
+    };
+    let required_annotations =
+        |alternative: BTreeMap<String, String>| (!alternative.is_empty()).then_some(alternative);
+
+    let response = match annotations.alternatives().as_slice() {
+        [alternative] => verify_signature_with_annotations(
+            signature,
+            container_image,
+            namespace,
+            &required_annotations(alternative.clone()),
+        ),
+        alternatives => {
+            let mut errors: Vec<String> = vec![];
+            alternatives
+                .iter()
+                .map(|alternative| {
+                    verify_signature_with_annotations(
+                        signature,
+                        container_image,
+                        namespace,
+                        &required_annotations(alternative.clone()),
+                    )
+                    .inspect_err(|e| errors.push(e.to_string()))
+                })
+                .find(Result::is_ok)
+                .unwrap_or_else(|| {
+                    Err(anyhow::anyhow!(
+                        "no signature has the annotations {annotations}: [{}]",
+                        errors.join("; ")
+                    ))
+                })
+        }
+    }?;
+
+    for (key, value) in annotations.forbidden() {
+        let forbidden_annotation = BTreeMap::from([(key.to_string(), value.to_string())]);
+        if let Some(signer) =
+            forbidden_signer(signature, container_image, namespace, forbidden_annotation)
+        {
+            return Err(anyhow::anyhow!(
+                "{signer} signed the image with the forbidden annotation {key}={value}"
+            ));
+        }
+    }
+
+    Ok(response)
+}
+
+/// Find a signer of the rule that has signed the image with the forbidden
+/// annotation. Each signer is verified on its own: the rule does not have to
+/// be satisfied by the forbidden signatures for the image to be rejected.
+fn forbidden_signer(
+    signature: &Signature,
+    container_image: &str,
+    namespace: &Namespace,
+    forbidden_annotation: BTreeMap<String, String>,
+) -> Option<String> {
+    let annotations = Some(forbidden_annotation);
+    match signature {
+        Signature::PubKeys(s) => active_pems(&s.pub_keys, Utc::now())
+            .into_iter()
+            .find(|(_, pub_key)| {
+                verify_pub_keys_image(container_image, vec![pub_key.clone()], annotations.clone())
+                    .is_ok()
+            })
+            .map(|(index, _)| format!("pubKeys[{index}]")),
+        Signature::Keyless(s) => {
+            s.keyless
+                .iter()
+                .find(|keyless| {
+                    match &s.certificate_extensions {
+                        Some(certificate_extensions) => signature_identities(container_image)
+                            .and_then(|identities| {
+                                verify_keyless_identity(
+                                    &identities,
+                                    keyless,
+                                    certificate_extensions,
+                                    &annotations,
+                                )
+                            }),
+                        None => verify_keyless_exact_match(
+                            container_image,
+                            vec![(*keyless).clone()],
+                            annotations.clone(),
+                        ),
+                    }
+                    .is_ok()
+                })
+                .map(|keyless| format!("{} ({})", keyless.subject, keyless.issuer))
+        }
+        Signature::KeylessPrefix(s) => {
+            s.keyless_prefix
+                .iter()
+                .find(|prefix| {
+                    match &s.certificate_extensions {
+                        Some(certificate_extensions) => signature_identities(container_image)
+                            .and_then(|identities| {
+                                verify_keyless_prefix_identity(
+                                    &identities,
+                                    prefix,
+                                    certificate_extensions,
+                                    &annotations,
+                                )
+                            }),
+                        None => verify_keyless_prefix_match(
+                            container_image,
+                            vec![(*prefix).clone()],
+                            annotations.clone(),
+                        ),
+                    }
+                    .is_ok()
+                })
+                .map(|prefix| format!("{} ({})", prefix.url_prefix, prefix.issuer))
+        }
+        Signature::KeylessMatch(s) => {
+            let identities = signature_identities(container_image).ok()?;
+            s.keyless_match
+                .iter()
+                .find(|pattern| {
+                    verify_certificate_identities(
+                        &identities,
+                        &format!("the keyless identity {pattern}"),
+                        |identity| pattern.matches(identity),
+                        &annotations,
+                    )
+                    .is_ok()
+                })
+                .map(|pattern| pattern.to_string())
+        }
+        Signature::Certificate(s) => {
+            let now = Utc::now();
+            let crls = s.crls.as_deref().unwrap_or_default();
+            let certificate_chain = active_certificate_chain(s, now);
+            let crl_issuers: Vec<&str> = certificate_chain
+                .iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            active_pems(&s.certificates, now)
+                .into_iter()
+                .find(|(_, certificate)| {
+                    revocation(certificate, crls, &crl_issuers).is_none()
+                        && verify_certificate(
+                            container_image,
+                            certificate.clone(),
+                            certificate_chain.clone(),
+                            s.require_rekor_bundle,
+                            annotations.clone(),
+                        )
+                        .is_ok()
+                })
+                .map(|(index, _)| format!("certificates[{index}]"))
+        }
+        // These rules accept the signers of the image that satisfy their
+        // constraints, any of them is enough to verify the rule
+        Signature::GithubActions(_)
+        | Signature::GitlabCi(_)
+        | Signature::LeafCertificate(_)
+        | Signature::Group(_) => {
+            verify_signature_with_annotations(signature, container_image, namespace, &annotations)
+                .is_ok()
+                .then(|| "a signer of the rule".to_string())
+        }
+    }
+}
+
+/// Verify the image against a single signature rule, requiring the signatures
+/// to have the given annotations
+fn verify_signature_with_annotations(
+    signature: &Signature,
+    container_image: &str,
+    namespace: &Namespace,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    match signature {
+        Signature::PubKeys(s) => {
+            let pub_keys = active_pems(&s.pub_keys, Utc::now());
+            match s.required_signers() {
+                _ if pub_keys.is_empty() && !s.pub_keys.is_empty() => Err(anyhow::anyhow!(
+                    "none of the pub keys is valid at this time"
+                )),
+                None => verify_pub_keys_image(
+                    container_image,
+                    pub_keys.into_iter().map(|(_, pub_key)| pub_key).collect(),
+                    annotations.clone(),
                 ),
-                Signature::GithubActions(s) => verify_keyless_github_actions(
-                    container_image.as_str(),
-                    s.github_actions.owner.clone(),
-                    s.github_actions.repo.clone(),
-                    s.annotations.clone(),
+                Some(threshold) => verify_signers_threshold(
+                    &pub_keys,
+                    threshold,
+                    |(_, pub_key)| signer_identity(pub_key),
+                    |_, (index, _)| format!("pubKeys[{index}]"),
+                    |(_, pub_key)| {
+                        verify_pub_keys_image(
+                            container_image,
+                            vec![pub_key.clone()],
+                            annotations.clone(),
+                        )
+                    },
                 ),
-                Signature::Certificate(s) => {
+            }
+        }
+        Signature::Keyless(s) if s.certificate_extensions.is_some() => {
+            verify_keyless_certificate_extensions(s, container_image, annotations)
+        }
+        Signature::Keyless(s) => match s.required_signers() {
+            None => {
+                verify_keyless_exact_match(container_image, s.keyless.clone(), annotations.clone())
+            }
+            Some(threshold) => verify_signers_threshold(
+                &s.keyless,
+                threshold,
+                |keyless| (keyless.issuer.clone(), keyless.subject.clone()),
+                |_, keyless| format!("{} ({})", keyless.subject, keyless.issuer),
+                |keyless| {
+                    verify_keyless_exact_match(
+                        container_image,
+                        vec![keyless.clone()],
+                        annotations.clone(),
+                    )
+                },
+            ),
+        },
+        Signature::KeylessPrefix(s) if s.certificate_extensions.is_some() => {
+            verify_keyless_prefix_certificate_extensions(s, container_image, annotations)
+        }
+        Signature::KeylessPrefix(s) => verify_keyless_prefix_match(
+            container_image,
+            s.keyless_prefix.clone(),
+            annotations.clone(),
+        ),
+        Signature::KeylessMatch(s) => verify_keyless_match(s, container_image, annotations),
+        Signature::GithubActions(s)
+            if s.github_actions.has_workflow_constraints()
+                || s.certificate_extensions.is_some() =>
+        {
+            verify_github_actions_workflow(s, container_image, annotations)
+        }
+        Signature::GithubActions(s) => verify_keyless_github_actions(
+            container_image,
+            s.github_actions.owner.clone(),
+            s.github_actions.repo.clone(),
+            annotations.clone(),
+        ),
+        Signature::GitlabCi(s) => verify_gitlab_ci(s, container_image, annotations),
+        Signature::Group(group) => verify_signature_group(group, container_image, namespace),
+        Signature::LeafCertificate(s) => verify_leaf_certificate(s, container_image, annotations),
+        Signature::Certificate(s) => {
+            let now = Utc::now();
+            let crls = s.crls.as_deref().unwrap_or_default();
+            let certificate_chain = active_certificate_chain(s, now);
+            let crl_issuers: Vec<&str> = certificate_chain
+                .iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            let certificates: Vec<(usize, String, Option<Revocation>)> =
+                active_pems(&s.certificates, now)
+                    .into_iter()
+                    .map(|(index, certificate)| {
+                        let revocation = revocation(&certificate, crls, &crl_issuers);
+                        (index, certificate, revocation)
+                    })
+                    .collect();
+            match s.required_signers() {
+                _ if certificates.is_empty() && !s.certificates.is_empty() => Err(anyhow::anyhow!(
+                    "none of the certificates is valid at this time"
+                )),
+                None => {
                     let mut response: Result<VerificationResponse> =
                         Err(anyhow::anyhow!("Cannot verify"));
 
-                    for (index, certificate) in s.certificates.iter().enumerate() {
+                    for (index, certificate, revocation) in certificates {
+                        if let Some(revocation) = revocation {
+                            response = Err(anyhow::anyhow!(
+                                "certificates[{index}] has been rejected: {revocation}"
+                            ));
+                            break;
+                        }
                         response = verify_certificate(
-                            container_image.as_str(),
-                            certificate.clone(),
-                            s.certificate_chain.clone(),
+                            container_image,
+                            certificate,
+                            certificate_chain.clone(),
                             s.require_rekor_bundle,
-                            s.annotations.clone(),
+                            annotations.clone(),
                         );
                         // All the certificates must be verified. As soon as one of
                         // them cannot be used to verify the image -> break from the
@@ -390,7 +948,7 @@ where
                             warn!(
                                 LOG_DRAIN,
                                 "certificate image verification failed";
-                                "image" => container_image.clone(),
+                                "image" => container_image.to_string(),
                                 "certificate-index" => index,
                             );
                             break;
@@ -398,22 +956,431 @@ where
                     }
                     response
                 }
-            };
+                Some(threshold) => verify_signers_threshold(
+                    &certificates,
+                    threshold,
+                    |(_, certificate, _)| signer_identity(certificate),
+                    |_, (index, _, revocation)| match revocation {
+                        Some(revocation) => format!("certificates[{index}] ({revocation})"),
+                        None => format!("certificates[{index}]"),
+                    },
+                    |(_, certificate, revocation)| {
+                        if revocation.is_some() {
+                            return Err(anyhow::anyhow!("certificate revoked"));
+                        }
+                        verify_certificate(
+                            container_image,
+                            certificate.clone(),
+                            certificate_chain.clone(),
+                            s.require_rekor_bundle,
+                            annotations.clone(),
+                        )
+                    },
+                ),
+            }
+        }
+    }
+}
 
-            handle_verification_response(
-                verification_response,
-                container_image.as_str(),
-                &mut container_with_images_digests[i],
-                policy_verification_errors,
-            );
+/// The certificates of the chain of the rule that are valid at this time
+fn active_certificate_chain(certificate: &Certificate, now: DateTime<Utc>) -> Option<Vec<String>> {
+    certificate.certificate_chain.as_ref().map(|chain| {
+        active_pems(chain, now)
+            .into_iter()
+            .map(|(_, certificate)| certificate)
+            .collect()
+    })
+}
+
+/// Verify the image against the certificates embedded in its signatures that
+/// satisfy the leaf constraints. The certificates are only used to select the
+/// candidates, each candidate is then verified by the host against the
+/// certificate chain of the rule and the digest whose signatures have been
+/// read.
+fn verify_leaf_certificate(
+    leaf_certificate: &LeafCertificate,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let certificate_chain: Vec<String> =
+        active_pems(&leaf_certificate.certificate_chain, Utc::now())
+            .into_iter()
+            .map(|(_, certificate)| certificate)
+            .collect();
+    if certificate_chain.is_empty() {
+        return Err(anyhow::anyhow!(
+            "none of the certificates of the chain is valid at this time"
+        ));
+    }
+
+    let signature_certificates = signature_certificates(container_image)?;
+    let certificates = &signature_certificates.certificates;
+    let describe = |certificate: &str| {
+        certificate_subject(certificate).unwrap_or_else(|| "invalid certificate".to_string())
+    };
+    let candidates: Vec<&String> = certificates
+        .iter()
+        .filter(|certificate| leaf_certificate.leaf_certificate.matches(certificate))
+        .collect();
+    if candidates.is_empty() {
+        return Err(anyhow::anyhow!(
+            "no signature certificate matches {}; certificates found: [{}]",
+            leaf_certificate.leaf_certificate,
+            certificates
+                .iter()
+                .map(|certificate| describe(certificate))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    let crls = leaf_certificate.crls.as_deref().unwrap_or_default();
+    let crl_issuers: Vec<&str> = certificate_chain.iter().map(String::as_str).collect();
+    let mut errors: Vec<String> = vec![];
+    for certificate in candidates {
+        if let Some(revocation) = revocation(certificate, crls, &crl_issuers) {
+            errors.push(format!("{}: {revocation}", describe(certificate)));
+            continue;
+        }
+        match verify_certificate(
+            &signature_certificates.image,
+            certificate.clone(),
+            Some(certificate_chain.clone()),
+            leaf_certificate.require_rekor_bundle,
+            annotations.clone(),
+        ) {
+            Ok(response) => return Ok(response),
+            Err(e) => errors.push(format!("{}: {e}", describe(certificate))),
         }
     }
 
-    if containers != container_with_images_digests {
-        Some(container_with_images_digests.to_vec())
-    } else {
-        None
+    Err(anyhow::anyhow!(
+        "none of the signature certificates matching {} can be verified: [{}]",
+        leaf_certificate.leaf_certificate,
+        errors.join("; ")
+    ))
+}
+
+/// Verify the rules of a group that apply to the image. The digest is taken
+/// from the satisfied rules, which must all agree on it.
+fn verify_signature_group(
+    group: &SignatureGroup,
+    container_image: &str,
+    namespace: &Namespace,
+) -> Result<VerificationResponse> {
+    let rules: Vec<&Signature> = group
+        .rules()
+        .iter()
+        .filter(|rule| signature_applies(rule, container_image, namespace))
+        .collect();
+    if rules.is_empty() {
+        return Err(anyhow::anyhow!("no rule of the group applies to the image"));
+    }
+
+    let mut errors: Vec<String> = vec![];
+    match group.rule {
+        GroupRule::AllOf(_) => {
+            let mut responses: Vec<VerificationResponse> = vec![];
+            for rule in rules {
+                match verify_signature(rule, container_image, namespace) {
+                    Ok(response) => responses.push(response),
+                    Err(e) => errors.push(format!("{rule}: {e}")),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "allOf group not satisfied: [{}]",
+                    errors.join("; ")
+                ));
+            }
+
+            let response = responses.remove(0);
+            if let Some(other) = responses.iter().find(|r| r.digest != response.digest) {
+                return Err(anyhow::anyhow!(
+                    "the rules of the allOf group verified different digests: {} and {}",
+                    response.digest,
+                    other.digest
+                ));
+            }
+            Ok(response)
+        }
+        GroupRule::AnyOf(_) => {
+            for rule in rules {
+                match verify_signature(rule, container_image, namespace) {
+                    Ok(response) => return Ok(response),
+                    Err(e) => errors.push(format!("{rule}: {e}")),
+                }
+            }
+            Err(anyhow::anyhow!(
+                "none of the rules of the anyOf group is satisfied: [{}]",
+                errors.join("; ")
+            ))
+        }
+    }
+}
+
+/// Verify the image against keyless identity patterns. Each pattern must be
+/// satisfied by at least one of the signatures.
+fn verify_keyless_match(
+    keyless_match: &KeylessMatch,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = signature_identities(container_image)?;
+
+    let mut response = Err(anyhow::anyhow!("no keyless identity provided"));
+    for pattern in &keyless_match.keyless_match {
+        response = Ok(verify_certificate_identities(
+            &identities,
+            &format!("the keyless identity {pattern}"),
+            |identity| pattern.matches(identity),
+            annotations,
+        )?);
+    }
+
+    response
+}
+
+/// Verify the image against the keyless identities, requiring the
+/// certificates of the signatures to have the given Fulcio extensions
+fn verify_keyless_certificate_extensions(
+    keyless: &Keyless,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = signature_identities(container_image)?;
+    let certificate_extensions = keyless.certificate_extensions.clone().unwrap_or_default();
+
+    verify_signers_threshold(
+        &keyless.keyless,
+        keyless.required_signers().unwrap_or(keyless.keyless.len()),
+        |keyless| (keyless.issuer.clone(), keyless.subject.clone()),
+        |_, keyless| format!("{} ({})", keyless.subject, keyless.issuer),
+        |keyless_info| {
+            verify_keyless_identity(
+                &identities,
+                keyless_info,
+                &certificate_extensions,
+                annotations,
+            )
+        },
+    )
+}
+
+/// Verify the image against a keyless identity whose certificates must have
+/// the given Fulcio extensions
+fn verify_keyless_identity(
+    identities: &SignatureIdentities,
+    keyless_info: &KeylessInfo,
+    certificate_extensions: &CertificateExtensions,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    verify_certificate_identities(
+        identities,
+        &format!(
+            "the keyless identity {} ({}) with the certificate extensions {certificate_extensions}",
+            keyless_info.subject, keyless_info.issuer
+        ),
+        |identity| {
+            identity.issuer == keyless_info.issuer
+                && identity.subject == keyless_info.subject
+                && certificate_extensions.matches(identity)
+        },
+        annotations,
+    )
+}
+
+/// Verify the image against the keyless prefixes, requiring the certificates
+/// of the signatures to have the given Fulcio extensions
+fn verify_keyless_prefix_certificate_extensions(
+    keyless_prefix: &KeylessPrefix,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = signature_identities(container_image)?;
+    let certificate_extensions = keyless_prefix
+        .certificate_extensions
+        .clone()
+        .unwrap_or_default();
+
+    let mut response = Err(anyhow::anyhow!("no keyless prefix provided"));
+    for prefix in &keyless_prefix.keyless_prefix {
+        response = Ok(verify_keyless_prefix_identity(
+            &identities,
+            prefix,
+            &certificate_extensions,
+            annotations,
+        )?);
+    }
+
+    response
+}
+
+/// Verify the image against a keyless prefix whose certificates must have
+/// the given Fulcio extensions
+fn verify_keyless_prefix_identity(
+    identities: &SignatureIdentities,
+    prefix: &KeylessPrefixInfo,
+    certificate_extensions: &CertificateExtensions,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    // Like the host, the prefix is matched as a whole path to prevent
+    // typosquatting: `https://github.com/acme` does not match `acme-fork`
+    let url_prefix = match prefix.url_prefix.ends_with('/') {
+        true => prefix.url_prefix.clone(),
+        false => format!("{}/", prefix.url_prefix),
+    };
+    verify_certificate_identities(
+        identities,
+        &format!(
+            "the keyless prefix {url_prefix} ({}) with the certificate extensions {certificate_extensions}",
+            prefix.issuer
+        ),
+        |identity| {
+            identity.issuer == prefix.issuer
+                && identity.subject.starts_with(&url_prefix)
+                && certificate_extensions.matches(identity)
+        },
+        annotations,
+    )
+}
+
+/// Verify the image against the GitHub Actions workflow constraints that
+/// cannot be checked by the host, like the workflow path, the trigger event
+/// or the certificate extensions
+fn verify_github_actions_workflow(
+    github_actions: &GithubActions,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = signature_identities(container_image)?;
+    let mut description = format!(
+        "the GitHub Actions workflow {}",
+        github_actions.github_actions
+    );
+    if let Some(certificate_extensions) = &github_actions.certificate_extensions {
+        description.push_str(&format!(
+            " with the certificate extensions {certificate_extensions}"
+        ));
+    }
+
+    verify_certificate_identities(
+        &identities,
+        &description,
+        |identity| {
+            github_actions.github_actions.matches(identity)
+                && github_actions
+                    .certificate_extensions
+                    .as_ref()
+                    .is_none_or(|extensions| extensions.matches(identity))
+        },
+        annotations,
+    )
+}
+
+/// Verify the image against the GitLab CI pipeline constraints
+fn verify_gitlab_ci(
+    gitlab_ci: &GitlabCi,
+    container_image: &str,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = signature_identities(container_image)?;
+    verify_certificate_identities(
+        &identities,
+        &format!("the GitLab CI pipeline {}", gitlab_ci.gitlab_ci),
+        |identity| gitlab_ci.gitlab_ci.matches(identity),
+        annotations,
+    )
+}
+
+/// Verify the image against the signers whose certificates are accepted. The
+/// certificates of the signatures are only used to select the candidates,
+/// each candidate is then verified by the host as an exact keyless identity
+/// against the digest whose signatures have been read.
+/// The host can trust any signature of a given signer, hence a signer is a
+/// candidate only when all of its certificates are accepted.
+fn verify_certificate_identities(
+    signature_identities: &SignatureIdentities,
+    description: &str,
+    accepts: impl Fn(&CertificateIdentity) -> bool,
+    annotations: &Option<BTreeMap<String, String>>,
+) -> Result<VerificationResponse> {
+    let identities = &signature_identities.identities;
+    let mut candidates: Vec<&CertificateIdentity> = vec![];
+    for identity in identities {
+        let is_accepted = identities
+            .iter()
+            .filter(|other| other.same_signer(identity))
+            .all(&accepts);
+        if is_accepted && !candidates.iter().any(|c| c.same_signer(identity)) {
+            candidates.push(identity);
+        }
+    }
+    if candidates.is_empty() {
+        return Err(anyhow::anyhow!(
+            "no signature matches {description}; {signature_identities}"
+        ));
+    }
+
+    candidates
+        .iter()
+        .map(|identity| {
+            verify_keyless_exact_match(
+                &signature_identities.image,
+                vec![KeylessInfo {
+                    issuer: identity.issuer.clone(),
+                    subject: identity.subject.clone(),
+                }],
+                annotations.clone(),
+            )
+        })
+        .find(Result::is_ok)
+        .unwrap_or_else(|| {
+            Err(anyhow::anyhow!(
+                "no signature of {description} can be verified; {signature_identities}"
+            ))
+        })
+}
+
+/// Verify the image against each signer individually. The verification
+/// succeeds as soon as `threshold` signers have been found, otherwise the
+/// error lists the signers that have been found and the missing ones.
+/// The signers are counted by identity: a signer listed twice is verified,
+/// and counted, once.
+fn verify_signers_threshold<S, I: PartialEq>(
+    signers: &[S],
+    threshold: usize,
+    identity: impl Fn(&S) -> I,
+    describe: impl Fn(usize, &S) -> String,
+    verify: impl Fn(&S) -> Result<VerificationResponse>,
+) -> Result<VerificationResponse> {
+    let mut found: Vec<String> = vec![];
+    let mut missing: Vec<String> = vec![];
+    let mut seen: Vec<I> = vec![];
+
+    for (index, signer) in signers.iter().enumerate() {
+        let signer_identity = identity(signer);
+        if seen.contains(&signer_identity) {
+            continue;
+        }
+        seen.push(signer_identity);
+        match verify(signer) {
+            Ok(response) => {
+                found.push(describe(index, signer));
+                if found.len() >= threshold {
+                    return Ok(response);
+                }
+            }
+            Err(_) => missing.push(describe(index, signer)),
+        }
     }
+
+    Err(anyhow::anyhow!(
+        "{} of the {threshold} required signatures found; found: [{}], missing: [{}]",
+        found.len(),
+        found.join(", "),
+        missing.join(", ")
+    ))
 }
 
 fn handle_verification_response<T>(
@@ -455,14 +1422,30 @@ fn add_digest_if_not_present<T>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cosign::tests::{
+        mock_signatures, DEVELOPER_CERTIFICATE, GITLAB_CERTIFICATE, IMAGE_DIGEST,
+        PULL_REQUEST_CERTIFICATE, RELEASE_CERTIFICATE,
+    };
+    use crate::settings::crl::tests::{
+        ALICE_CERTIFICATE, BOB_CERTIFICATE, CA_CERTIFICATE, CA_CRL, CA_CRL_BASE64,
+    };
+    use crate::settings::leaf_certificate::tests as leaf;
+    use crate::settings::public_key::tests::P256_PUBLIC_KEY;
     use crate::settings::{
-        github_actions::KeylessGithubActionsInfo, Certificate, GithubActions, Keyless,
-        KeylessPrefix, PubKeys,
+        certificate_extensions::CertificateExtensions,
+        github_actions::{KeylessGithubActionsInfo, RunnerEnvironment},
+        gitlab_ci::KeylessGitlabCiInfo,
+        keyless_match::{KeylessIdentityPattern, MatchType},
+        rule_scope::RuleScope,
+        Certificate, GithubActions, ImageRegex, Keyless, KeylessPrefix, PubKeys, Requester,
+        SkipImage,
     };
     use anyhow::anyhow;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
     use kubewarden::{
         host_capabilities::verification::{KeylessInfo, KeylessPrefixInfo, VerificationResponse},
-        request::{GroupVersionKind, KubernetesAdmissionRequest},
+        request::{GroupVersionKind, KubernetesAdmissionRequest, UserInfo},
         response::ValidationResponse,
         test::Testcase,
     };
@@ -470,6 +1453,7 @@ mod tests {
     use rstest::*;
     use serde_json::json;
     use serial_test::serial;
+    use std::collections::BTreeMap;
 
     #[automock()]
     pub mod crypto_sdk {
@@ -486,6 +1470,39 @@ mod tests {
         }
     }
 
+    #[automock()]
+    pub mod oci_sdk {
+        use anyhow::Result;
+        use kubewarden::host_capabilities::oci::{ManifestDigestResponse, OciManifestResponse};
+
+        // needed for creating mocks
+        #[allow(dead_code)]
+        pub fn get_manifest_digest(_image: &str) -> Result<ManifestDigestResponse> {
+            Ok(ManifestDigestResponse {
+                digest: "mock_digest".to_string(),
+            })
+        }
+
+        // needed for creating mocks
+        #[allow(dead_code)]
+        pub fn get_manifest(_image: &str) -> Result<OciManifestResponse> {
+            Err(anyhow::anyhow!("manifest not found"))
+        }
+    }
+
+    #[automock()]
+    pub mod kubernetes_sdk {
+        use anyhow::Result;
+        use kubewarden::host_capabilities::kubernetes::GetResourceRequest;
+        use serde::de::DeserializeOwned;
+
+        // needed for creating mocks
+        #[allow(dead_code)]
+        pub fn get_resource<T: DeserializeOwned + 'static>(_req: &GetResourceRequest) -> Result<T> {
+            Err(anyhow::anyhow!("resource not found"))
+        }
+    }
+
     #[automock()]
     pub mod verification_sdk {
         use anyhow::Result;
@@ -811,10 +1828,11 @@ mod tests {
             let settings: Settings = Settings {
                 signatures: vec![Signature::PubKeys(PubKeys {
                     image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
-                    pub_keys: vec!["key".to_string()],
-                    annotations: None,
+                    pub_keys: vec!["key".into()],
+                    ..Default::default()
                 })],
                 modify_images_with_digest: allow_mutation,
+                ..Default::default()
             };
 
             let request = ValidationRequest {
@@ -852,10 +1870,11 @@ mod tests {
         let settings: Settings = Settings {
             signatures: vec![Signature::PubKeys(PubKeys {
                 image: "*".to_string(),
-                pub_keys: vec!["key".to_string()],
-                annotations: None,
+                pub_keys: vec!["key".into()],
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -889,9 +1908,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     subject: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -934,9 +1954,10 @@ mod tests {
             signatures: vec![Signature::Keyless(Keyless {
                 image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
                 keyless: vec![],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -969,12 +1990,13 @@ mod tests {
         let settings: Settings = Settings {
             signatures: vec![Signature::Certificate(Certificate {
                 image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
-                certificates: vec!["good-cert".to_string()],
+                certificates: vec!["good-cert".into()],
                 certificate_chain: None,
                 require_rekor_bundle: true,
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: false,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1008,12 +2030,13 @@ mod tests {
         let settings: Settings = Settings {
             signatures: vec![Signature::Certificate(Certificate {
                 image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
-                certificates: vec!["good-cert1".to_string(), "good-cert2".to_string()],
+                certificates: vec!["good-cert1".into(), "good-cert2".into()],
                 certificate_chain: None,
                 require_rekor_bundle: true,
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: false,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1048,12 +2071,13 @@ mod tests {
         let settings: Settings = Settings {
             signatures: vec![Signature::Certificate(Certificate {
                 image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
-                certificates: vec!["good-cert".to_string(), "bad-cert".to_string()],
+                certificates: vec!["good-cert".into(), "bad-cert".into()],
                 certificate_chain: None,
                 require_rekor_bundle: true,
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1086,15 +2110,16 @@ mod tests {
                 Signature::PubKeys(PubKeys {
                     image: "no_matching".to_string(),
                     pub_keys: vec![],
-                    annotations: None,
+                    ..Default::default()
                 }),
                 Signature::Keyless(Keyless {
                     image: "no_matching".to_string(),
                     keyless: vec![],
-                    annotations: None,
+                    ..Default::default()
                 }),
             ],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1135,15 +2160,16 @@ mod tests {
                         issuer: "issuer".to_string(),
                         subject: "subject".to_string(),
                     }],
-                    annotations: None,
+                    ..Default::default()
                 }),
                 Signature::PubKeys(PubKeys {
                     image: "init".to_string(),
                     pub_keys: vec![],
-                    annotations: None,
+                    ..Default::default()
                 }),
             ],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1187,15 +2213,16 @@ mod tests {
                         issuer: "issuer".to_string(),
                         subject: "subject".to_string(),
                     }],
-                    annotations: None,
+                    ..Default::default()
                 }),
                 Signature::PubKeys(PubKeys {
                     image: "init".to_string(),
                     pub_keys: vec![],
-                    annotations: None,
+                    ..Default::default()
                 }),
             ],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1253,9 +2280,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     subject: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1289,9 +2317,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     url_prefix: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1324,10 +2353,12 @@ mod tests {
                 github_actions: KeylessGithubActionsInfo {
                     owner: "owner".to_string(),
                     repo: Some("repo".to_string()),
+                    ..Default::default()
                 },
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1342,6 +2373,1849 @@ mod tests {
         assert!(response.mutated_object.is_none())
     }
 
+    fn github_actions_workflow_settings() -> Settings {
+        Settings {
+            signatures: vec![Signature::GithubActions(GithubActions {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                github_actions: KeylessGithubActionsInfo {
+                    owner: "acme".to_string(),
+                    repo: Some("app".to_string()),
+                    workflow_path: Some(".github/workflows/release.yml".to_string()),
+                    refs: Some(vec!["refs/tags/v*".to_string()]),
+                    trigger_events: Some(vec!["push".to_string()]),
+                    runner_environment: Some(RunnerEnvironment::GithubHosted),
+                },
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn github_actions_workflow_validation_pass() {
+        let _ctx_oci = mock_signatures(&[PULL_REQUEST_CERTIFICATE, RELEASE_CERTIFICATE]);
+        let ctx_github_actions = mock_verification_sdk::verify_keyless_github_actions_context();
+        ctx_github_actions.expect().times(0);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, keyless, _| {
+                *image
+                    == format!("ghcr.io/kubewarden/test-verify-image-signatures@{IMAGE_DIGEST}")
+                    && keyless[0].subject
+                        == "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+            })
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by the release workflow"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: github_actions_workflow_settings(),
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn github_actions_workflow_validation_dont_pass_for_pull_requests() {
+        let _ctx_oci = mock_signatures(&[PULL_REQUEST_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect().times(0);
+
+        let tc = Testcase {
+            name: String::from("It should reject the image signed by a pull request"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: github_actions_workflow_settings(),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: no signature matches the GitHub Actions workflow acme/app, workflow .github/workflows/release.yml, refs [refs/tags/v*], trigger events [push], runner environment github-hosted; identities found: [https://github.com/acme/app/.github/workflows/release.yml@refs/pull/42/merge (https://token.actions.githubusercontent.com)]"
+        );
+    }
+
+    fn annotations_settings() -> Settings {
+        Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                pub_keys: vec!["key".into()],
+                scope: RuleScope {
+                    annotations: Some(
+                        serde_json::from_value(json!({
+                            "env": "prod",
+                            "tier": { "oneOf": ["gold", "silver"] },
+                            "stage": { "forbidden": ["dev"] }
+                        }))
+                        .unwrap(),
+                    ),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::not_forbidden("test", true)]
+    #[case::forbidden("dev", false)]
+    #[serial]
+    fn annotations_operators_validation(
+        #[case] signed_stage: &'static str,
+        #[case] expected_validation_result: bool,
+    ) {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect().times(3).returning(
+            move |_, _, annotations: Option<BTreeMap<String, String>>| {
+                // The image has been signed with env=prod, tier=silver and
+                // stage=signed_stage
+                let annotations = annotations.unwrap_or_default();
+                let signed = annotations.iter().all(|(key, value)| {
+                    matches!(
+                        (key.as_str(), value.as_str()),
+                        ("env", "prod") | ("tier", "silver")
+                    ) || (key == "stage" && value == signed_stage)
+                });
+                match signed {
+                    true => Ok(VerificationResponse {
+                        is_trusted: true,
+                        digest: IMAGE_DIGEST.to_string(),
+                    }),
+                    false => Err(anyhow!("annotations not satisfied")),
+                }
+            },
+        );
+
+        let tc = Testcase {
+            name: String::from("It should check the annotation operators"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: annotations_settings(),
+            expected_validation_result,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(response.accepted, expected_validation_result);
+        if !expected_validation_result {
+            assert_eq!(
+                response.message.unwrap(),
+                "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: pubKeys[0] signed the image with the forbidden annotation stage=dev"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::no_forbidden_signature(None, true)]
+    #[case::one_forbidden_signature(Some("bob"), false)]
+    #[serial]
+    fn forbidden_annotations_are_checked_signer_by_signer(
+        #[case] forbidden_signer: Option<&'static str>,
+        #[case] expected_validation_result: bool,
+    ) {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect().times(3).returning(
+            move |_, pub_keys: Vec<String>, annotations: Option<BTreeMap<String, String>>| {
+                // Both keys signed the image, only the forbidden signer with
+                // stage=dev
+                let signed = match annotations {
+                    None => true,
+                    Some(annotations) => {
+                        annotations.get("stage").map(String::as_str) == Some("dev")
+                            && pub_keys
+                                .iter()
+                                .all(|key| Some(key.as_str()) == forbidden_signer)
+                    }
+                };
+                match signed {
+                    true => Ok(VerificationResponse {
+                        is_trusted: true,
+                        digest: IMAGE_DIGEST.to_string(),
+                    }),
+                    false => Err(anyhow!("annotations not satisfied")),
+                }
+            },
+        );
+
+        let settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                pub_keys: vec!["alice".into(), "bob".into()],
+                scope: RuleScope {
+                    annotations: Some(
+                        serde_json::from_value(json!({ "stage": { "forbidden": ["dev"] } }))
+                            .unwrap(),
+                    ),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should check the forbidden annotations of each signer"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(response.accepted, expected_validation_result);
+        if !expected_validation_result {
+            assert_eq!(
+                response.message.unwrap(),
+                "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: pubKeys[1] signed the image with the forbidden annotation stage=dev"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn annotations_operators_validation_dont_pass_without_alternative() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, _, _| Err(anyhow!("annotations not satisfied")));
+
+        let tc = Testcase {
+            name: String::from("It should reject the image without the annotations"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: annotations_settings(),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: no signature has the annotations [env=prod, stage not in [dev], tier one of [gold, silver]]: [annotations not satisfied; annotations not satisfied]"
+        );
+    }
+
+    fn certificate_extensions(extensions: serde_json::Value) -> Option<CertificateExtensions> {
+        Some(serde_json::from_value(extensions).expect("cannot deserialize the extensions"))
+    }
+
+    #[rstest]
+    #[case::matching(json!({ "runnerEnvironment": "github-hosted", "buildTrigger": "push" }), true)]
+    #[case::missing_extension(json!({ "sourceRepositoryURI": "https://github.com/acme/*" }), false)]
+    #[serial]
+    fn keyless_certificate_extensions_validation(
+        #[case] extensions: serde_json::Value,
+        #[case] expected_validation_result: bool,
+    ) {
+        let _ctx_oci = mock_signatures(&[RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(usize::from(expected_validation_result))
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let settings = Settings {
+            signatures: vec![Signature::Keyless(Keyless {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                keyless: vec![KeylessInfo {
+                    issuer: "https://token.actions.githubusercontent.com".to_string(),
+                    subject:
+                        "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+                            .to_string(),
+                }],
+                certificate_extensions: certificate_extensions(extensions),
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should check the certificate extensions"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(response.accepted, expected_validation_result);
+    }
+
+    #[test]
+    #[serial]
+    fn keyless_prefix_certificate_extensions_validation_pass() {
+        let _ctx_oci = mock_signatures(&[PULL_REQUEST_CERTIFICATE, RELEASE_CERTIFICATE]);
+        let ctx_prefix = mock_verification_sdk::verify_keyless_prefix_match_context();
+        ctx_prefix.expect().times(0);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, keyless, _| {
+                keyless[0].subject
+                    == "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+            })
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let settings = Settings {
+            signatures: vec![Signature::KeylessPrefix(KeylessPrefix {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                keyless_prefix: vec![KeylessPrefixInfo {
+                    issuer: "https://token.actions.githubusercontent.com".to_string(),
+                    url_prefix: "https://github.com/acme".to_string(),
+                }],
+                certificate_extensions: certificate_extensions(json!({ "buildTrigger": "push" })),
+                ..Default::default()
+            })],
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed on push"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    fn gitlab_ci_settings(refs: Vec<String>) -> Settings {
+        Settings {
+            signatures: vec![Signature::GitlabCi(GitlabCi {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                gitlab_ci: KeylessGitlabCiInfo {
+                    namespace: "acme/platform".to_string(),
+                    project: Some("app".to_string()),
+                    refs: Some(refs),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn gitlab_ci_validation_pass() {
+        let _ctx_oci = mock_signatures(&[GITLAB_CERTIFICATE, RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, keyless, _| {
+                keyless[0].issuer == "https://gitlab.com"
+                    && keyless[0].subject
+                        == "https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/tags/v2.0.0"
+            })
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by the GitLab pipeline"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: gitlab_ci_settings(vec!["refs/tags/v*".to_string()]),
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn gitlab_ci_validation_dont_pass_for_other_refs() {
+        let _ctx_oci = mock_signatures(&[GITLAB_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect().times(0);
+
+        let tc = Testcase {
+            name: String::from("It should reject the image not signed on the main branch"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: gitlab_ci_settings(vec!["refs/heads/main".to_string()]),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: no signature matches the GitLab CI pipeline acme/platform/app, refs [refs/heads/main] (https://gitlab.com); identities found: [https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/tags/v2.0.0 (https://gitlab.com)]"
+        );
+    }
+
+    fn keyless_match_settings(subject: &str) -> Settings {
+        Settings {
+            signatures: vec![Signature::KeylessMatch(KeylessMatch {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                keyless_match: vec![KeylessIdentityPattern::new(
+                    r"^https://token\.actions\.githubusercontent\.com$",
+                    subject,
+                    MatchType::Regex,
+                )
+                .expect("cannot compile the patterns")],
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn keyless_match_validation_pass() {
+        let _ctx_oci = mock_signatures(&[DEVELOPER_CERTIFICATE, RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, keyless, _| {
+                keyless[0].subject
+                    == "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+            })
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by the release workflow"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: keyless_match_settings(
+                r"^https://github\.com/acme/[^/]+/\.github/workflows/release\.yml@refs/tags/v\d+",
+            ),
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn keyless_match_validation_dont_pass_without_matching_identity() {
+        let _ctx_oci = mock_signatures(&[DEVELOPER_CERTIFICATE, RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect().times(0);
+
+        let tc = Testcase {
+            name: String::from("It should reject the image not signed by the CI workflow"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: keyless_match_settings(
+                r"^https://github\.com/acme/app/\.github/workflows/ci\.yml@",
+            ),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: no signature matches the keyless identity ^https://github\\.com/acme/app/\\.github/workflows/ci\\.yml@ (^https://token\\.actions\\.githubusercontent\\.com$); identities found: [alice@acme.org (https://accounts.google.com), https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0 (https://token.actions.githubusercontent.com)]"
+        );
+    }
+
+    fn leaf_certificate_settings(leaf_certificate: serde_json::Value) -> Settings {
+        Settings {
+            signatures: vec![Signature::LeafCertificate(LeafCertificate {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                certificate_chain: vec![leaf::CA_CERTIFICATE.into()],
+                leaf_certificate: serde_json::from_value(leaf_certificate)
+                    .expect("cannot deserialize the leaf constraints"),
+                require_rekor_bundle: true,
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn leaf_certificate_validation_pass() {
+        let _ctx_oci = mock_signatures(&[leaf::SERVER_CERTIFICATE, leaf::RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, certificate, chain, require_rekor_bundle, _| {
+                *image == format!("ghcr.io/kubewarden/test-verify-image-signatures@{IMAGE_DIGEST}")
+                    && certificate == leaf::RELEASE_CERTIFICATE
+                    && *chain == Some(vec![leaf::CA_CERTIFICATE.to_string()])
+                    && *require_rekor_bundle
+            })
+            .returning(|_, _, _, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by a code signing certificate"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: leaf_certificate_settings(json!({
+                "subject": "O=Acme, *",
+                "extendedKeyUsages": ["codeSigning"]
+            })),
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn leaf_certificate_validation_dont_pass_without_matching_certificate() {
+        let _ctx_oci = mock_signatures(&[leaf::SERVER_CERTIFICATE, leaf::RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect().times(0);
+
+        let tc = Testcase {
+            name: String::from("It should reject the image not signed by the release bot"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: leaf_certificate_settings(json!({ "emails": ["*@example.com"] })),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: no signature certificate matches emails [*@example.com]; certificates found: [O=Acme, CN=www.acme.org, O=Acme, OU=Release Engineering, CN=release-bot]"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn leaf_certificate_validation_dont_pass_when_not_verified() {
+        let _ctx_oci = mock_signatures(&[leaf::RELEASE_CERTIFICATE]);
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect()
+            .times(1)
+            .returning(|_, _, _, _, _| Err(anyhow!("certificate not issued by the CA")));
+
+        let tc = Testcase {
+            name: String::from("It should reject the image when the host cannot verify it"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: leaf_certificate_settings(json!({ "extendedKeyUsages": ["codeSigning"] })),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert!(response.message.unwrap().ends_with(
+            "none of the signature certificates matching extended key usages [codeSigning] can be verified: [O=Acme, OU=Release Engineering, CN=release-bot: certificate not issued by the CA]"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_threshold_validation_pass() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(3)
+            .returning(|_, pub_keys, _| match pub_keys[0].as_str() {
+                "good-key1" | "good-key2" => Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                }),
+                _ => Err(anyhow!("not a good key")),
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                pub_keys: vec![
+                    "bad-key".into(),
+                    "good-key1".into(),
+                    "good-key2".into(),
+                    "other-key".into(),
+                ],
+                threshold: Some(2),
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by 2 of the keys"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_threshold_validation_dont_pass() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(3)
+            .returning(|_, pub_keys, _| match pub_keys[0].as_str() {
+                "good-key" => Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                }),
+                _ => Err(anyhow!("not a good key")),
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                pub_keys: vec!["bad-key1".into(), "good-key".into(), "bad-key2".into()],
+                threshold: Some(2),
+                ..Default::default()
+            })],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should reject the image signed by only 1 of the keys"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: verification of image ghcr.io/kubewarden/test-verify-image-signatures:signed failed: 1 of the 2 required signatures found; found: [pubKeys[1]], missing: [pubKeys[0], pubKeys[2]]"
+        );
+    }
+
+    #[test]
+    fn signers_threshold_counts_duplicated_signers_once() {
+        let verified = std::cell::Cell::new(0);
+        let response = verify_signers_threshold(
+            &["good-key", "good-key"],
+            2,
+            |key| signer_identity(key),
+            |index, _| format!("pubKeys[{index}]"),
+            |_| {
+                verified.set(verified.get() + 1);
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "sha256:89102e34".to_string(),
+                })
+            },
+        );
+        assert_eq!(verified.get(), 1);
+        assert_eq!(
+            response.err().unwrap().to_string(),
+            "1 of the 2 required signatures found; found: [pubKeys[0]], missing: []"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_outside_their_validity_window_are_ignored() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, pub_keys, _| *pub_keys == ["current-key"])
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                })
+            });
+
+        let signature: Signature = serde_json::from_value(json!({
+            "image": "ghcr.io/kubewarden/test-verify-image-signatures:*",
+            "pubKeys": [
+                { "pem": "retired-key", "notAfter": "2020-01-01T00:00:00Z" },
+                "current-key",
+                { "pem": "next-key", "notBefore": "2100-01-01T00:00:00Z" }
+            ]
+        }))
+        .expect("cannot deserialize signature");
+        let settings: Settings = Settings {
+            signatures: vec![signature],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should verify the image with the current key only"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    fn config_map_pub_key_settings() -> Settings {
+        Settings {
+            signatures: vec![serde_json::from_value(json!({
+                "image": "ghcr.io/kubewarden/test-verify-image-signatures:*",
+                "pubKeys": [
+                    { "configMapRef": { "namespace": "security", "name": "signing-keys", "key": "cosign.pub" } }
+                ]
+            }))
+            .expect("cannot deserialize signature")],
+            modify_images_with_digest: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_from_config_map() {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect::<ConfigMap>()
+            .times(1)
+            .withf(|req| req.name == "signing-keys" && req.namespace.as_deref() == Some("security"))
+            .returning(|_| {
+                Ok(ConfigMap {
+                    data: Some(BTreeMap::from([(
+                        "cosign.pub".to_string(),
+                        P256_PUBLIC_KEY.to_string(),
+                    )])),
+                    ..Default::default()
+                })
+            });
+
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, pub_keys, _| *pub_keys == [P256_PUBLIC_KEY])
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                })
+            });
+
+        let tc = Testcase {
+            name: String::from("It should verify the image with the key of the ConfigMap"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: config_map_pub_key_settings(),
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_from_config_map_not_read_for_other_images() {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes.expect::<ConfigMap>().times(0);
+
+        let settings = Settings {
+            signatures: vec![serde_json::from_value(json!({
+                "image": "ghcr.io/acme/*",
+                "pubKeys": [
+                    { "configMapRef": { "namespace": "security", "name": "signing-keys", "key": "cosign.pub" } }
+                ]
+            }))
+            .expect("cannot deserialize signature")],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should not read the ConfigMap of the rules that do not apply"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_from_config_map_holding_a_certificate_rejects() {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect::<ConfigMap>()
+            .times(1)
+            .returning(|_| {
+                Ok(ConfigMap {
+                    data: Some(BTreeMap::from([(
+                        "cosign.pub".to_string(),
+                        RELEASE_CERTIFICATE.to_string(),
+                    )])),
+                    ..Default::default()
+                })
+            });
+
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect().times(0);
+
+        let tc = Testcase {
+            name: String::from("It should reject the request when the ConfigMap holds no key"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: config_map_pub_key_settings(),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: cannot load the keys of Pub key signature for image ghcr.io/kubewarden/test-verify-image-signatures:*: pubKeys[0]: a certificate has been provided instead of a public key, use a certificate rule instead"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn pub_keys_from_missing_config_map_rejects() {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect::<ConfigMap>()
+            .times(1)
+            .returning(|_| Err(anyhow!("configmaps \"signing-keys\" not found")));
+
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect().times(0).returning(|_, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                    .to_string(),
+            })
+        });
+
+        let tc = Testcase {
+            name: String::from("It should reject the request when the key cannot be read"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings: config_map_pub_key_settings(),
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: cannot load the keys of Pub key signature for image ghcr.io/kubewarden/test-verify-image-signatures:*: pubKeys[0]: cannot read the key cosign.pub of ConfigMap security/signing-keys: configmaps \"signing-keys\" not found"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn signature_rule_outside_its_validity_window_is_disabled() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect().times(0).returning(|_, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                    .to_string(),
+            })
+        });
+
+        let signature: Signature = serde_json::from_value(json!({
+            "image": "ghcr.io/kubewarden/test-verify-image-signatures:*",
+            "pubKeys": ["key"],
+            "notBefore": "2100-01-01T00:00:00Z"
+        }))
+        .expect("cannot deserialize signature");
+        let settings: Settings = Settings {
+            signatures: vec![signature],
+            unmatched_images: UnmatchedImages::Deny,
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should not enforce a rule before its validity window"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: no signature rule matches the images: [ghcr.io/kubewarden/test-verify-image-signatures:signed]"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn keyless_any_of_signers_validation_pass() {
+        let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, keyless, _| match keyless[0].subject.as_str() {
+                "good-subject" => Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                }),
+                _ => Err(anyhow!("not a good subject")),
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::Keyless(Keyless {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                keyless: vec![
+                    KeylessInfo {
+                        issuer: "issuer".to_string(),
+                        subject: "bad-subject".to_string(),
+                    },
+                    KeylessInfo {
+                        issuer: "issuer".to_string(),
+                        subject: "good-subject".to_string(),
+                    },
+                ],
+                any_of_signers: true,
+                ..Default::default()
+            })],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should accept the image signed by any of the identities"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+        assert!(response.mutated_object.is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn certificate_threshold_validation_dont_pass() {
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, _, _, _, _| Err(anyhow!("not good-cert")));
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::Certificate(Certificate {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                certificates: vec!["bad-cert1".into(), "bad-cert2".into()],
+                require_rekor_bundle: true,
+                any_of_signers: true,
+                ..Default::default()
+            })],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should reject the image signed by none of the certificates"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert!(response
+            .message
+            .unwrap()
+            .ends_with("0 of the 1 required signatures found; found: [], missing: [certificates[0], certificates[1]]"));
+    }
+
+    #[test]
+    #[serial]
+    fn certificate_validation_rejects_revoked_certificates() {
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, certificate, _, _, _| certificate == ALICE_CERTIFICATE)
+            .returning(|_, _, _, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                })
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::Certificate(Certificate {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                certificates: vec![ALICE_CERTIFICATE.into(), BOB_CERTIFICATE.into()],
+                certificate_chain: Some(vec![CA_CERTIFICATE.into()]),
+                crls: Some(vec![CA_CRL.to_string()]),
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should reject the image signed with a revoked certificate"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        let message = response.message.unwrap();
+        assert!(
+            message.contains(
+                "certificates[1] has been rejected: certificate with serial 10:02 revoked on"
+            ),
+            "{message}"
+        );
+        assert!(message.ends_with("reason: KeyCompromise"));
+    }
+
+    #[test]
+    #[serial]
+    fn certificate_threshold_validation_skips_revoked_certificates() {
+        let ctx = mock_verification_sdk::verify_certificate_context();
+        ctx.expect()
+            .times(1)
+            .withf(|_, certificate, _, _, _| certificate == ALICE_CERTIFICATE)
+            .returning(|_, _, _, _, _| Err(anyhow!("not signed by alice")));
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::Certificate(Certificate {
+                image: "ghcr.io/kubewarden/test-verify-image-signatures:*".to_string(),
+                certificates: vec![BOB_CERTIFICATE.into(), ALICE_CERTIFICATE.into()],
+                certificate_chain: Some(vec![CA_CERTIFICATE.into()]),
+                crls: Some(vec![CA_CRL_BASE64.to_string()]),
+                any_of_signers: true,
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should not count the revoked certificates"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        let message = response.message.unwrap();
+        assert!(
+            message.contains("missing: [certificates[0] (certificate with serial 10:02 revoked on"),
+            "{message}"
+        );
+        assert!(message.contains("reason: KeyCompromise), certificates[1]]"));
+    }
+
+    fn ci_or_vendor_and_security_team_group() -> Signature {
+        serde_json::from_value(json!({
+            "image": "ghcr.io/kubewarden/test-verify-image-signatures:*",
+            "allOf": [
+                {
+                    "anyOf": [
+                        { "githubActions": { "owner": "kubewarden" } },
+                        { "certificates": ["vendor-cert"], "requireRekorBundle": true }
+                    ]
+                },
+                { "pubKeys": ["security-team-key"] }
+            ]
+        }))
+        .expect("cannot deserialize group")
+    }
+
+    #[test]
+    #[serial]
+    fn group_validation_pass_with_mutation() {
+        let ctx_github = mock_verification_sdk::verify_keyless_github_actions_context();
+        ctx_github
+            .expect()
+            .times(1)
+            .returning(|_, _, _, _| Err(anyhow!("not signed by CI")));
+
+        let ctx_certificate = mock_verification_sdk::verify_certificate_context();
+        ctx_certificate
+            .expect()
+            .times(1)
+            .returning(|_, _, _, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                })
+            });
+
+        let ctx_pub_keys = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx_pub_keys.expect().times(1).returning(|_, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                    .to_string(),
+            })
+        });
+
+        let settings: Settings = Settings {
+            signatures: vec![ci_or_vendor_and_security_team_group()],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from(
+                "It should accept the image signed by the vendor and the security team",
+            ),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+        assert_eq!(
+            response.mutated_object.unwrap()["spec"]["containers"][0]["image"],
+            image_url(true)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn group_validation_dont_pass_when_all_of_is_not_satisfied() {
+        let ctx_github = mock_verification_sdk::verify_keyless_github_actions_context();
+        ctx_github.expect().times(1).returning(|_, _, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                    .to_string(),
+            })
+        });
+
+        let ctx_certificate = mock_verification_sdk::verify_certificate_context();
+        ctx_certificate
+            .expect()
+            .times(0)
+            .returning(|_, _, _, _, _| Err(anyhow!("error")));
+
+        let ctx_pub_keys = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx_pub_keys
+            .expect()
+            .times(1)
+            .returning(|_, _, _| Err(anyhow!("not signed by the security team")));
+
+        let settings: Settings = Settings {
+            signatures: vec![ci_or_vendor_and_security_team_group()],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should reject the image not signed by the security team"),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+        assert!(response.message.unwrap().ends_with(
+            "allOf group not satisfied: [Pub key signature for image ghcr.io/kubewarden/test-verify-image-signatures:*: not signed by the security team]"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn group_validation_dont_pass_when_digests_differ() {
+        let ctx_github = mock_verification_sdk::verify_keyless_github_actions_context();
+        ctx_github.expect().times(1).returning(|_, _, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                    .to_string(),
+            })
+        });
+
+        let ctx_pub_keys = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx_pub_keys.expect().times(1).returning(|_, _, _| {
+            Ok(VerificationResponse {
+                is_trusted: true,
+                digest: "sha256:a3d850c2022ebf02156114178ef35298d63f83c740e7b5dd7777ff05898880f8"
+                    .to_string(),
+            })
+        });
+
+        let settings: Settings = Settings {
+            signatures: vec![ci_or_vendor_and_security_team_group()],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from(
+                "It should reject the image when the rules verified different digests",
+            ),
+            fixture_file: String::from("test_data/pod_creation_signed.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(!response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn validation_skips_excluded_images() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, _, _| image == "nginx")
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest:
+                        "sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+                            .to_string(),
+                })
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "*".to_string(),
+                scope: RuleScope {
+                    exclude_images: Some(vec!["ini?".to_string()]),
+                    ..Default::default()
+                },
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should verify only the nginx container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+        let mutated_object = response.mutated_object.unwrap();
+        assert_eq!(mutated_object["spec"]["initContainers"][0]["image"], "init");
+    }
+
+    #[test]
+    #[serial]
+    fn validation_matches_normalized_images() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, _, _| image == "nginx")
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "docker.io/library/nginx:*".to_string(),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should verify the nginx container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn validation_rejects_short_names() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(0)
+            .returning(|_, _, _| Err(anyhow!("error")));
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "docker.io/library/nginx:*".to_string(),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            reject_short_names: true,
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should reject the images without registry"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: false,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response
+            .message
+            .unwrap()
+            .contains("image nginx does not name its registry"));
+    }
+
+    fn nginx_only_settings(unmatched_images: UnmatchedImages) -> Settings {
+        Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "nginx".to_string(),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            unmatched_images,
+            modify_images_with_digest: false,
+            ..Default::default()
+        }
+    }
+
+    fn expect_nginx_verification() -> mock_verification_sdk::__verify_pub_keys_image::Context {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, _, _| image == "nginx")
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+        ctx
+    }
+
+    #[rstest]
+    #[case::allow(UnmatchedImages::Allow, true, None)]
+    #[case::warn(
+        UnmatchedImages::Warn,
+        true,
+        Some("no signature rule matches the images: [init]")
+    )]
+    #[case::deny(
+        UnmatchedImages::Deny,
+        false,
+        Some("Resource nginx is not accepted: no signature rule matches the images: [init]")
+    )]
+    #[serial]
+    fn unmatched_images_modes(
+        #[case] unmatched_images: UnmatchedImages,
+        #[case] accepted: bool,
+        #[case] expected_message: Option<&str>,
+    ) {
+        let _ctx = expect_nginx_verification();
+
+        let tc = Testcase {
+            name: String::from("Unmatched init container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings: nginx_only_settings(unmatched_images),
+            expected_validation_result: accepted,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        let message = if accepted {
+            response.warnings.map(|warnings| warnings.join(", "))
+        } else {
+            response.message
+        };
+        assert_eq!(message.as_deref(), expected_message);
+    }
+
+    #[test]
+    #[serial]
+    fn unmatched_images_deny_accepts_exempted_images() {
+        let _ctx = expect_nginx_verification();
+
+        let mut settings = nginx_only_settings(UnmatchedImages::Deny);
+        settings.skip_images = vec![SkipImage {
+            image: "init".to_string(),
+            reason: "built in house".to_string(),
+            expires: None,
+        }];
+
+        let tc = Testcase {
+            name: String::from("Exempted init container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        assert!(tc.eval(validate).unwrap().accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn validation_matches_images_with_regex() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, _, _| image == "nginx")
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image_regex: Some(ImageRegex::try_from("^(nginx|busybox)(:.+)?$").unwrap()),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should verify only the nginx container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn skipped_images_are_accepted_and_reported() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(|image, _, _| image == "nginx")
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+
+        let settings: Settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "*".to_string(),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            skip_images: vec![
+                SkipImage {
+                    image: "init".to_string(),
+                    reason: "vendor image, see SEC-42".to_string(),
+                    expires: Some("2100-01-01T00:00:00Z".parse().unwrap()),
+                },
+                SkipImage {
+                    image: "nginx".to_string(),
+                    reason: "expired exemption".to_string(),
+                    expires: Some("2020-01-01T00:00:00Z".parse().unwrap()),
+                },
+            ],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        let tc = Testcase {
+            name: String::from("It should accept the skipped init container"),
+            fixture_file: String::from("test_data/pod_creation_with_init_container.json"),
+            settings,
+            expected_validation_result: true,
+        };
+
+        let response = tc.eval(validate).unwrap();
+        assert!(response.accepted);
+        let exemption =
+            "image init exempted by skipped image init: vendor image, see SEC-42".to_string();
+        assert_eq!(response.warnings.unwrap(), vec![exemption.clone()]);
+        assert_eq!(
+            response.audit_annotations.unwrap()["exempted-images"],
+            exemption
+        );
+    }
+
+    fn validate_pod_in_namespace(namespace: &str, settings: Settings) -> ValidationResponse {
+        let request = ValidationRequest {
+            request: KubernetesAdmissionRequest {
+                kind: GroupVersionKind {
+                    kind: "Pod".to_string(),
+                    ..Default::default()
+                },
+                namespace: namespace.to_string(),
+                object: pod(false),
+                ..Default::default()
+            },
+            settings,
+        };
+
+        let response = validate(serde_json::to_vec(&request).unwrap().as_slice()).unwrap();
+        serde_json::from_slice(&response).unwrap()
+    }
+
+    fn platform_and_tenant_settings() -> Settings {
+        Settings {
+            signatures: vec![
+                Signature::PubKeys(PubKeys {
+                    image: "*".to_string(),
+                    scope: RuleScope {
+                        namespaces: Some(vec!["kube-*".to_string()]),
+                        ..Default::default()
+                    },
+                    pub_keys: vec!["platform-key".into()],
+                    ..Default::default()
+                }),
+                Signature::PubKeys(PubKeys {
+                    image: "*".to_string(),
+                    scope: RuleScope {
+                        exclude_namespaces: Some(vec!["kube-*".to_string()]),
+                        ..Default::default()
+                    },
+                    pub_keys: vec!["tenant-key".into()],
+                    ..Default::default()
+                }),
+            ],
+            excluded_namespaces: vec!["sandbox-?".to_string()],
+            modify_images_with_digest: false,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::platform("kube-system", "platform-key")]
+    #[case::tenant("tenant-a", "tenant-key")]
+    #[serial]
+    fn namespace_scoped_signatures(#[case] namespace: &str, #[case] expected_key: &'static str) {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(move |_, pub_keys, _| pub_keys == &vec![expected_key.to_string()])
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+
+        let response = validate_pod_in_namespace(namespace, platform_and_tenant_settings());
+        assert!(response.accepted);
+    }
+
+    #[test]
+    #[serial]
+    fn excluded_namespaces_are_not_verified() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(0)
+            .returning(|_, _, _| Err(anyhow!("error")));
+
+        let response = validate_pod_in_namespace("sandbox-1", platform_and_tenant_settings());
+        assert!(response.accepted);
+    }
+
+    fn opt_in_settings() -> Settings {
+        Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "*".to_string(),
+                scope: RuleScope {
+                    namespace_selector: Some(LabelSelector {
+                        match_labels: Some(BTreeMap::from([(
+                            "verify-images.acme.io/enforce".to_string(),
+                            "true".to_string(),
+                        )])),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::opted_in(Some("true"), 1)]
+    #[case::opted_out(Some("false"), 0)]
+    #[case::not_labeled(None, 0)]
+    #[serial]
+    fn namespace_selector_opt_in(
+        #[case] label: Option<&'static str>,
+        #[case] verifications: usize,
+    ) {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect()
+            .times(1)
+            .withf(|req| req.kind == "Namespace" && req.name == "tenant-a")
+            .returning(move |_| {
+                Ok(Namespace {
+                    metadata: ObjectMeta {
+                        name: Some("tenant-a".to_string()),
+                        labels: label.map(|value| {
+                            BTreeMap::from([(
+                                "verify-images.acme.io/enforce".to_string(),
+                                value.to_string(),
+                            )])
+                        }),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            });
+
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(verifications)
+            .returning(|_, _, _| Err(anyhow!("not signed")));
+
+        let response = validate_pod_in_namespace("tenant-a", opt_in_settings());
+        assert_eq!(response.accepted, verifications == 0);
+    }
+
+    #[test]
+    #[serial]
+    fn namespace_selector_lookup_failure_rejects() {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect::<Namespace>()
+            .times(1)
+            .returning(|_| Err(anyhow!("namespace not found")));
+
+        let response = validate_pod_in_namespace("tenant-a", opt_in_settings());
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "Resource nginx is not accepted: cannot get namespace tenant-a: namespace not found"
+        );
+    }
+
+    #[rstest]
+    #[case::labeled(Some("prod"), true)]
+    #[case::not_labeled(None, false)]
+    #[serial]
+    fn annotation_templates_from_namespace(
+        #[case] env: Option<&'static str>,
+        #[case] accepted: bool,
+    ) {
+        let ctx_kubernetes = mock_kubernetes_sdk::get_resource_context();
+        ctx_kubernetes
+            .expect()
+            .times(1)
+            .withf(|req| req.kind == "Namespace" && req.name == "payments")
+            .returning(move |_| {
+                Ok(Namespace {
+                    metadata: ObjectMeta {
+                        name: Some("payments".to_string()),
+                        labels: env
+                            .map(|env| BTreeMap::from([("env".to_string(), env.to_string())])),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            });
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(usize::from(accepted))
+            .withf(|_, _, annotations| {
+                annotations.as_ref().is_some_and(|annotations| {
+                    annotations["env"] == "prod" && annotations["namespace"] == "payments"
+                })
+            })
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: IMAGE_DIGEST.to_string(),
+                })
+            });
+
+        let settings = Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "*".to_string(),
+                pub_keys: vec!["key".into()],
+                scope: RuleScope {
+                    annotations: Some(
+                        serde_json::from_value(json!({
+                            "env": "{{ namespace.labels.env }}",
+                            "namespace": "{{ request.namespace }}"
+                        }))
+                        .unwrap(),
+                    ),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })],
+            modify_images_with_digest: false,
+            ..Default::default()
+        };
+
+        let response = validate_pod_in_namespace("payments", settings);
+        assert_eq!(response.accepted, accepted);
+        if !accepted {
+            assert_eq!(
+                response.message.unwrap(),
+                "Resource nginx is not accepted: cannot resolve the annotations of Pub key signature for image *: annotation env: namespace.labels.env is not set"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::name("nginx", json!({ "metadata": { "name": "nginx" } }), "nginx")]
+    #[case::generate_name("", json!({ "metadata": { "generateName": "nginx-7d9f-" } }), "nginx-7d9f-")]
+    #[case::no_name("", json!({ "metadata": {} }), "")]
+    fn name_of_the_templates(
+        #[case] name: &str,
+        #[case] object: serde_json::Value,
+        #[case] expected: &str,
+    ) {
+        let request = KubernetesAdmissionRequest {
+            name: name.to_string(),
+            object,
+            ..Default::default()
+        };
+        assert_eq!(template_name(&request), expected);
+    }
+
+    fn validate_pod_as(username: &str, groups: &[&str], settings: Settings) -> ValidationResponse {
+        let request = ValidationRequest {
+            request: KubernetesAdmissionRequest {
+                kind: GroupVersionKind {
+                    kind: "Pod".to_string(),
+                    ..Default::default()
+                },
+                user_info: UserInfo {
+                    username: username.to_string(),
+                    groups: groups.iter().map(|g| g.to_string()).collect(),
+                    ..Default::default()
+                },
+                object: pod(false),
+                ..Default::default()
+            },
+            settings,
+        };
+
+        let response = validate(serde_json::to_vec(&request).unwrap().as_slice()).unwrap();
+        serde_json::from_slice(&response).unwrap()
+    }
+
+    fn requesters_settings() -> Settings {
+        Settings {
+            signatures: vec![Signature::PubKeys(PubKeys {
+                image: "*".to_string(),
+                pub_keys: vec!["key".into()],
+                ..Default::default()
+            })],
+            requesters: vec![
+                Requester {
+                    usernames: Some(vec!["system:serviceaccount:kube-system:*".to_string()]),
+                    reason: "cluster components use upstream images".to_string(),
+                    ..Default::default()
+                },
+                Requester {
+                    groups: Some(vec!["acme:operators".to_string()]),
+                    reason: "operators images are signed by the vendor".to_string(),
+                    signatures: Some(vec![Signature::PubKeys(PubKeys {
+                        image: "*".to_string(),
+                        pub_keys: vec!["vendor-key".into()],
+                        ..Default::default()
+                    })]),
+                    ..Default::default()
+                },
+            ],
+            modify_images_with_digest: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn exempted_requester_is_not_verified() {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(0)
+            .returning(|_, _, _| Err(anyhow!("error")));
+
+        let response = validate_pod_as(
+            "system:serviceaccount:kube-system:cluster-autoscaler",
+            &["system:serviceaccounts"],
+            requesters_settings(),
+        );
+
+        assert!(response.accepted);
+        let exemption = "request from system:serviceaccount:kube-system:cluster-autoscaler exempted: cluster components use upstream images";
+        assert_eq!(response.warnings.unwrap(), vec![exemption.to_string()]);
+        assert_eq!(
+            response.audit_annotations.unwrap()["exempted-requester"],
+            exemption
+        );
+    }
+
+    #[rstest]
+    #[case::requester_rules("operator", &["acme:operators"], "vendor-key")]
+    #[case::default_rules("alice", &["system:authenticated"], "key")]
+    #[serial]
+    fn requester_signatures(
+        #[case] username: &str,
+        #[case] groups: &[&str],
+        #[case] expected_key: &'static str,
+    ) {
+        let ctx = mock_verification_sdk::verify_pub_keys_image_context();
+        ctx.expect()
+            .times(1)
+            .withf(move |_, pub_keys, _| pub_keys == &vec![expected_key.to_string()])
+            .returning(|_, _, _| {
+                Ok(VerificationResponse {
+                    is_trusted: true,
+                    digest: "".to_string(),
+                })
+            });
+
+        let response = validate_pod_as(username, groups, requesters_settings());
+        assert!(response.accepted);
+        assert!(response.warnings.is_none());
+    }
+
     fn resource_validation_pass(file: &str) {
         let ctx = mock_verification_sdk::verify_keyless_exact_match_context();
         ctx.expect().times(1).returning(|_, _, _| {
@@ -1358,9 +4232,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     subject: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
@@ -1388,9 +4263,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     subject: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let tc = Testcase {
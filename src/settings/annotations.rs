@@ -0,0 +1,452 @@
+use k8s_openapi::api::core::v1::Namespace;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Maximum number of combinations of `oneOf` values, each combination is
+/// verified with a call to the host
+const MAX_ALTERNATIVES: usize = 32;
+
+const TEMPLATE_START: &str = "{{";
+const TEMPLATE_END: &str = "}}";
+pub(crate) const NAMESPACE_LABELS: &str = "namespace.labels.";
+pub(crate) const NAMESPACE_ANNOTATIONS: &str = "namespace.annotations.";
+
+/// Values of the admission request available to the annotation templates,
+/// e.g. `{{ namespace.labels.env }}` or `{{ request.namespace }}`
+pub(crate) struct TemplateContext<'a> {
+    /// Name of the admitted resource
+    pub(crate) name: &'a str,
+    /// Namespace of the admitted resource, with its labels and annotations
+    /// when they are used by the templates
+    pub(crate) namespace: &'a Namespace,
+}
+
+impl TemplateContext<'_> {
+    fn lookup(&self, variable: &str) -> Result<String, String> {
+        let metadata = &self.namespace.metadata;
+        let namespace_name = metadata.name.clone().unwrap_or_default();
+        let value = match variable {
+            "request.name" => Some(self.name.to_string()),
+            "request.namespace" | "namespace.name" => Some(namespace_name),
+            _ => {
+                if let Some(label) = variable.strip_prefix(NAMESPACE_LABELS) {
+                    metadata.labels.as_ref().and_then(|l| l.get(label)).cloned()
+                } else if let Some(annotation) = variable.strip_prefix(NAMESPACE_ANNOTATIONS) {
+                    metadata
+                        .annotations
+                        .as_ref()
+                        .and_then(|a| a.get(annotation))
+                        .cloned()
+                } else {
+                    return Err(format!("unknown template variable {variable}"));
+                }
+            }
+        };
+
+        value
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| format!("{variable} is not set"))
+    }
+}
+
+/// Returns true when the variable can be used by the templates
+fn is_known_variable(variable: &str) -> bool {
+    matches!(
+        variable,
+        "request.name" | "request.namespace" | "namespace.name"
+    ) || [NAMESPACE_LABELS, NAMESPACE_ANNOTATIONS]
+        .iter()
+        .any(|prefix| variable.len() > prefix.len() && variable.starts_with(prefix))
+}
+
+/// Replaces the `{{ variable }}` templates of the value with the result of
+/// the lookup
+fn render(value: &str, lookup: &impl Fn(&str) -> Result<String, String>) -> Result<String, String> {
+    let mut rendered = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(TEMPLATE_START) {
+        let (text, template) = rest.split_at(start);
+        let Some(end) = template.find(TEMPLATE_END) else {
+            return Err(format!("unterminated template in {value}"));
+        };
+        rendered.push_str(text);
+        rendered.push_str(&lookup(template[TEMPLATE_START.len()..end].trim())?);
+        rest = &template[end + TEMPLATE_END.len()..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Variables used by the templates of the value
+fn template_variables(value: &str) -> Vec<String> {
+    let mut variables = vec![];
+    let mut rest = value;
+    while let Some((_, template)) = rest.split_once(TEMPLATE_START) {
+        let Some((variable, after)) = template.split_once(TEMPLATE_END) else {
+            break;
+        };
+        variables.push(variable.trim().to_string());
+        rest = after;
+    }
+    variables
+}
+
+/// Requirement on a signature annotation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged, try_from = "AnnotationRequirementEntry")]
+pub(crate) enum AnnotationRequirement {
+    /// The annotation must have exactly this value
+    Value(String),
+    Operator(AnnotationOperator),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnnotationRequirementEntry {
+    Value(String),
+    Operator(BTreeMap<String, serde_json::Value>),
+}
+
+impl TryFrom<AnnotationRequirementEntry> for AnnotationRequirement {
+    type Error = String;
+
+    fn try_from(entry: AnnotationRequirementEntry) -> Result<Self, Self::Error> {
+        let operator = match entry {
+            AnnotationRequirementEntry::Value(value) => {
+                return Ok(AnnotationRequirement::Value(value))
+            }
+            AnnotationRequirementEntry::Operator(operator) => operator,
+        };
+        let mut operators = operator.into_iter();
+        let (Some((name, values)), None) = (operators.next(), operators.next()) else {
+            return Err("exactly one of oneOf or forbidden must be provided".to_string());
+        };
+        let values = || {
+            serde_json::from_value::<Vec<String>>(values.clone())
+                .map_err(|e| format!("invalid {name} values: {e}"))
+        };
+        match name.as_str() {
+            "oneOf" => Ok(AnnotationRequirement::Operator(AnnotationOperator::OneOf(
+                values()?,
+            ))),
+            "forbidden" => Ok(AnnotationRequirement::Operator(
+                AnnotationOperator::Forbidden(values()?),
+            )),
+            // The annotations are in the payload of the signatures, a layer
+            // blob that the host capabilities cannot fetch. The host only
+            // compares exact values, so glob, regex or exists cannot be
+            // evaluated by the policy.
+            _ => Err(format!(
+                "unsupported annotation operator {name}, only oneOf and forbidden are supported: the signature payloads cannot be read by the policy, list the accepted values with oneOf instead"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum AnnotationOperator {
+    /// The annotation must have one of these values
+    OneOf(Vec<String>),
+    /// The signatures must not have the annotation with any of these values
+    Forbidden(Vec<String>),
+}
+
+/// Annotations that must, or must not, have been provided by the signers
+/// when they signed the OCI artifact
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(transparent)]
+pub(crate) struct Annotations(BTreeMap<String, AnnotationRequirement>);
+
+impl From<BTreeMap<String, String>> for Annotations {
+    fn from(annotations: BTreeMap<String, String>) -> Self {
+        Annotations(
+            annotations
+                .into_iter()
+                .map(|(key, value)| (key, AnnotationRequirement::Value(value)))
+                .collect(),
+        )
+    }
+}
+
+impl Annotations {
+    /// Sets of annotations that satisfy the requirements, one for each
+    /// combination of the `oneOf` values. The image is accepted when it has
+    /// been signed with any of them.
+    pub(crate) fn alternatives(&self) -> Vec<BTreeMap<String, String>> {
+        let mut alternatives = vec![BTreeMap::new()];
+        for (key, requirement) in &self.0 {
+            let values = match requirement {
+                AnnotationRequirement::Value(value) => std::slice::from_ref(value),
+                AnnotationRequirement::Operator(AnnotationOperator::OneOf(values)) => values,
+                AnnotationRequirement::Operator(AnnotationOperator::Forbidden(_)) => continue,
+            };
+            alternatives = alternatives
+                .into_iter()
+                .flat_map(|alternative| {
+                    values.iter().map(move |value| {
+                        let mut alternative = alternative.clone();
+                        alternative.insert(key.clone(), value.clone());
+                        alternative
+                    })
+                })
+                .collect();
+        }
+        alternatives
+    }
+
+    /// Annotations the signatures must not have
+    pub(crate) fn forbidden(&self) -> Vec<(&str, &str)> {
+        self.0
+            .iter()
+            .filter_map(|(key, requirement)| match requirement {
+                AnnotationRequirement::Operator(AnnotationOperator::Forbidden(values)) => Some(
+                    values
+                        .iter()
+                        .map(move |value| (key.as_str(), value.as_str())),
+                ),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Values of the requirements, including the `oneOf` and `forbidden` ones
+    fn values_mut(&mut self) -> impl Iterator<Item = (&String, &mut String)> {
+        self.0.iter_mut().flat_map(|(key, requirement)| {
+            match requirement {
+                AnnotationRequirement::Value(value) => std::slice::from_mut(value),
+                AnnotationRequirement::Operator(
+                    AnnotationOperator::OneOf(values) | AnnotationOperator::Forbidden(values),
+                ) => values.as_mut_slice(),
+            }
+            .iter_mut()
+            .map(move |value| (key, value))
+        })
+    }
+
+    /// Variables used by the templates of the values
+    pub(crate) fn template_variables(&self) -> Vec<String> {
+        self.0
+            .values()
+            .flat_map(|requirement| match requirement {
+                AnnotationRequirement::Value(value) => std::slice::from_ref(value),
+                AnnotationRequirement::Operator(
+                    AnnotationOperator::OneOf(values) | AnnotationOperator::Forbidden(values),
+                ) => values.as_slice(),
+            })
+            .flat_map(|value| template_variables(value))
+            .collect()
+    }
+
+    /// Replaces the templates of the values with the values of the
+    /// admission request
+    pub(crate) fn resolve_templates(&mut self, context: &TemplateContext) -> Result<(), String> {
+        for (key, value) in self.values_mut() {
+            *value = render(value, &|variable| context.lookup(variable))
+                .map_err(|e| format!("annotation {key}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let mut annotations = self.clone();
+        for (key, value) in annotations.values_mut() {
+            render(value, &|variable| match is_known_variable(variable) {
+                true => Ok(String::new()),
+                false => Err(format!("unknown template variable {variable}")),
+            })
+            .map_err(|e| format!("annotation {key}: {e}"))?;
+        }
+
+        for (key, requirement) in &self.0 {
+            if let AnnotationRequirement::Operator(
+                AnnotationOperator::OneOf(values) | AnnotationOperator::Forbidden(values),
+            ) = requirement
+            {
+                if values.is_empty() {
+                    return Err(format!("annotation {key}: no values provided"));
+                }
+            }
+        }
+
+        let alternatives = self.alternatives().len();
+        if alternatives > MAX_ALTERNATIVES {
+            return Err(format!(
+                "annotations: the oneOf values make {alternatives} combinations, at most {MAX_ALTERNATIVES} are supported"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Annotations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requirements: Vec<String> = self
+            .0
+            .iter()
+            .map(|(key, requirement)| match requirement {
+                AnnotationRequirement::Value(value) => format!("{key}={value}"),
+                AnnotationRequirement::Operator(AnnotationOperator::OneOf(values)) => {
+                    format!("{key} one of [{}]", values.join(", "))
+                }
+                AnnotationRequirement::Operator(AnnotationOperator::Forbidden(values)) => {
+                    format!("{key} not in [{}]", values.join(", "))
+                }
+            })
+            .collect();
+        write!(f, "[{}]", requirements.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn annotations(value: serde_json::Value) -> Annotations {
+        serde_json::from_value(value).expect("cannot deserialize annotations")
+    }
+
+    #[test]
+    fn deserialize_exact_values() {
+        assert_eq!(
+            annotations(json!({ "env": "prod" })),
+            Annotations::from(BTreeMap::from([("env".to_string(), "prod".to_string())]))
+        );
+
+        let result = serde_json::from_value::<Annotations>(json!({ "env": { "anyOf": ["prod"] } }));
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case::glob(
+        json!({ "env": { "glob": "prod-*" } }),
+        "unsupported annotation operator glob, only oneOf and forbidden are supported: the signature payloads cannot be read by the policy, list the accepted values with oneOf instead"
+    )]
+    #[case::exists(
+        json!({ "env": { "exists": true } }),
+        "unsupported annotation operator exists, only oneOf and forbidden are supported: the signature payloads cannot be read by the policy, list the accepted values with oneOf instead"
+    )]
+    #[case::many_operators(
+        json!({ "env": { "oneOf": ["prod"], "forbidden": ["dev"] } }),
+        "exactly one of oneOf or forbidden must be provided"
+    )]
+    #[case::invalid_values(
+        json!({ "env": { "oneOf": "prod" } }),
+        "invalid oneOf values: invalid type: string \"prod\", expected a sequence"
+    )]
+    fn deserialize_unsupported_operators(#[case] value: serde_json::Value, #[case] error: &str) {
+        let result = serde_json::from_value::<Annotations>(value);
+        assert_eq!(result.unwrap_err().to_string(), error);
+    }
+
+    #[test]
+    fn alternatives() {
+        let annotations = annotations(json!({
+            "env": "prod",
+            "tier": { "oneOf": ["gold", "silver"] },
+            "region": { "oneOf": ["eu", "us"] },
+            "stage": { "forbidden": ["dev"] }
+        }));
+
+        let alternatives = annotations.alternatives();
+        assert_eq!(alternatives.len(), 4);
+        assert!(alternatives
+            .iter()
+            .all(|a| a["env"] == "prod" && a.len() == 3));
+        assert!(alternatives
+            .iter()
+            .any(|a| a["tier"] == "silver" && a["region"] == "us"));
+        assert_eq!(annotations.forbidden(), vec![("stage", "dev")]);
+        assert_eq!(
+            annotations.to_string(),
+            "[env=prod, region one of [eu, us], stage not in [dev], tier one of [gold, silver]]"
+        );
+    }
+
+    fn namespace() -> Namespace {
+        serde_json::from_value(json!({
+            "metadata": {
+                "name": "payments",
+                "labels": { "env": "prod" },
+                "annotations": { "acme.org/team": "payments-team" }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve_templates() {
+        let mut annotations = annotations(json!({
+            "env": "{{ namespace.labels.env }}",
+            "deployment": "{{request.namespace}}/{{ request.name }}",
+            "team": { "oneOf": ["{{ namespace.annotations.acme.org/team }}", "platform"] }
+        }));
+        assert_eq!(
+            annotations.template_variables(),
+            vec![
+                "request.namespace",
+                "request.name",
+                "namespace.labels.env",
+                "namespace.annotations.acme.org/team"
+            ]
+        );
+
+        let namespace = namespace();
+        let context = TemplateContext {
+            name: "nginx",
+            namespace: &namespace,
+        };
+        annotations.resolve_templates(&context).unwrap();
+        assert_eq!(
+            annotations.to_string(),
+            "[deployment=payments/nginx, env=prod, team one of [payments-team, platform]]"
+        );
+    }
+
+    #[test]
+    fn resolve_templates_without_value() {
+        let mut annotations = annotations(json!({ "tier": "{{ namespace.labels.tier }}" }));
+        let namespace = namespace();
+        let context = TemplateContext {
+            name: "nginx",
+            namespace: &namespace,
+        };
+        assert_eq!(
+            annotations.resolve_templates(&context).unwrap_err(),
+            "annotation tier: namespace.labels.tier is not set"
+        );
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(
+            annotations(json!({ "env": "{{ namespace.env }}" }))
+                .validate()
+                .unwrap_err(),
+            "annotation env: unknown template variable namespace.env"
+        );
+        assert_eq!(
+            annotations(json!({ "env": "{{ namespace.labels.env" }))
+                .validate()
+                .unwrap_err(),
+            "annotation env: unterminated template in {{ namespace.labels.env"
+        );
+
+        assert_eq!(
+            annotations(json!({ "tier": { "oneOf": [] } }))
+                .validate()
+                .unwrap_err(),
+            "annotation tier: no values provided"
+        );
+
+        let values: Vec<String> = (0..6).map(|i| i.to_string()).collect();
+        let error = annotations(json!({ "a": { "oneOf": values }, "b": { "oneOf": values } }))
+            .validate()
+            .unwrap_err();
+        assert!(error.contains("36 combinations"), "{error}");
+    }
+}
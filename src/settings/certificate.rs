@@ -1,11 +1,21 @@
+use crate::LOG_DRAIN;
+
+use chrono::Utc;
 use kubewarden::host_capabilities::crypto::{
     BoolWithReason, Certificate as SDKCert, CertificateEncoding,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use slog::{info, warn};
 use std::{fmt, str};
 
-use super::validation_helpers::validate_vector_of_pem_strings;
+use super::certificate_chain::{chain_certificate_warnings, chain_path};
+use super::crl::{revocation, validate_crls};
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::pem_material::{
+    signer_identities, validate_pem_materials, validate_references, PemMaterial,
+};
+use super::rule_scope::RuleScope;
+use super::threshold::{required_signers, validate_distinct_signers, validate_threshold};
 
 #[cfg(test)]
 use crate::tests::mock_crypto_sdk::verify_cert;
@@ -13,16 +23,28 @@ use crate::tests::mock_crypto_sdk::verify_cert;
 #[cfg(not(test))]
 use kubewarden::host_capabilities::crypto::verify_cert;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Certificate {
     /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+    #[serde(default)]
     pub(crate) image: String,
-    /// PEM encoded certificate used to verify the signature
-    pub(crate) certificates: Vec<String>,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    /// PEM encoded certificates used to verify the signature, optionally with
+    /// the period of time during which they are used. Outside of it the
+    /// certificate is ignored
+    pub(crate) certificates: Vec<PemMaterial>,
     /// Optional - the certificate chain that is used to verify the provided
     /// certificate. When not specified, the certificate is assumed to be trusted
-    pub(crate) certificate_chain: Option<Vec<String>>,
+    pub(crate) certificate_chain: Option<Vec<PemMaterial>>,
+    /// Optional - CRLs, PEM encoded or as base64 encoded DER. The signatures
+    /// of the revoked certificates are rejected
+    pub(crate) crls: Option<Vec<String>>,
     /// Require the  signature layer to have a Rekor bundle.
     /// Having a Rekor bundle allows further checks to be performed,
     /// like ensuring the signature has been produced during the validity
@@ -31,36 +53,94 @@ pub(crate) struct Certificate {
     /// It is recommended to set this value to `true` to have a more secure
     /// verification process.
     pub(crate) require_rekor_bundle: bool,
-    /// Optional - Annotations that must have been provided by all signers when they signed the OCI artifact
-    pub(crate) annotations: Option<BTreeMap<String, String>>,
+    /// Optional - Minimum number of certificates that must have signed the
+    /// image. When not specified, all the certificates are required
+    pub(crate) threshold: Option<usize>,
+    /// Optional - Accept the image when it has been signed by any of the certificates
+    #[serde(default)]
+    pub(crate) any_of_signers: bool,
 }
 
 impl fmt::Display for Certificate {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Certificate signature for image {}", self.image)
+        write!(
+            f,
+            "Certificate signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
     }
 }
 impl Certificate {
+    /// Number of certificates that must have signed the image, `None` when all of them are required
+    pub(crate) fn required_signers(&self) -> Option<usize> {
+        required_signers(self.threshold, self.any_of_signers)
+    }
+
     pub(crate) fn validate(&self) -> Result<(), String> {
-        if self.image.is_empty() {
-            return Err("no image provided".to_string());
-        }
+        validate_image_pattern(&self.image, &self.image_regex).map_err(|e| e.to_string())?;
 
         if self.certificates.is_empty() {
             return Err("no certificate provided".to_string());
         }
 
+        validate_distinct_signers("certificates", signer_identities(&self.certificates))?;
+        validate_threshold(self.threshold, self.any_of_signers, self.certificates.len())?;
+
+        validate_references(&self.certificates).map_err(|e| e.code.to_string())?;
         if let Some(chain) = &self.certificate_chain {
-            validate_vector_of_pem_strings(chain).map_err(|e| e.code.to_string())?;
+            validate_pem_materials(chain).map_err(|e| e.code.to_string())?;
         }
+        // The certificates stored in the cluster can only be verified once
+        // they have been read, when the requests are validated
+        let has_chain_references = self
+            .certificate_chain
+            .iter()
+            .flatten()
+            .any(|c| c.reference.is_some());
+        let chain: Vec<&str> = self
+            .certificate_chain
+            .iter()
+            .flatten()
+            .map(|c| c.pem.as_str())
+            .collect();
 
+        if let Some(crls) = &self.crls {
+            let warnings =
+                validate_crls(crls, (!has_chain_references).then_some(&chain), Utc::now())?;
+            for warning in warnings {
+                warn!(
+                    LOG_DRAIN,
+                    "outdated CRL";
+                    "signature" => self.to_string(),
+                    "warning" => warning,
+                );
+            }
+            if let Some((index, revocation)) = self
+                .certificates
+                .iter()
+                .enumerate()
+                .filter(|_| !has_chain_references)
+                .find_map(|(index, c)| {
+                    revocation(&c.pem, crls, &chain).map(|revocation| (index, revocation))
+                })
+            {
+                return Err(format!("certificates[{index}]: {revocation}"));
+            }
+        }
+
+        if has_chain_references {
+            return Ok(());
+        }
+
+        // The bundles and the base64 encoded DER have been converted to PEM
+        // objects when the settings were deserialized
         let cert_chain_opt: Option<Vec<SDKCert>> = self.certificate_chain.as_ref().map({
             |chain| {
                 chain
                     .iter()
                     .map(|c| SDKCert {
                         encoding: CertificateEncoding::Pem,
-                        data: c.to_owned().into_bytes(),
+                        data: c.pem.clone().into_bytes(),
                     })
                     .collect()
             }
@@ -69,10 +149,11 @@ impl Certificate {
         let validation_errors: Vec<String> = self
             .certificates
             .iter()
+            .filter(|c| c.reference.is_none())
             .filter_map(|c| {
                 let sdk_cert = SDKCert {
                     encoding: CertificateEncoding::Pem,
-                    data: c.to_owned().into_bytes(),
+                    data: c.pem.clone().into_bytes(),
                 };
                 match verify_cert(sdk_cert, cert_chain_opt.clone(), None) {
                     Ok(b) => match b {
@@ -87,16 +168,71 @@ impl Certificate {
             })
             .collect();
 
-        if validation_errors.is_empty() {
-            Ok(())
-        } else {
-            Err(validation_errors.join("; "))
+        if !validation_errors.is_empty() {
+            return Err(validation_errors.join("; "));
+        }
+        if chain.is_empty() {
+            return Ok(());
+        }
+
+        // The host accepted the certificates, the problems of the chain are
+        // only reported
+        for warning in chain_certificate_warnings(&chain, Utc::now()) {
+            warn!(
+                LOG_DRAIN,
+                "invalid certificate of the chain";
+                "signature" => self.to_string(),
+                "warning" => warning,
+            );
+        }
+        let mut unused_chain_certificates: Vec<usize> = (0..chain.len()).collect();
+        for (index, certificate) in self.certificates.iter().enumerate() {
+            // certificates the host accepts but that cannot be parsed, e.g.
+            // stored in the cluster, are not reported
+            let Some(path) = chain_path(&certificate.pem, &chain) else {
+                continue;
+            };
+            let path = match path {
+                Ok(path) => path,
+                Err(e) => {
+                    warn!(
+                        LOG_DRAIN,
+                        "incomplete certificate chain path";
+                        "signature" => self.to_string(),
+                        "certificate" => format!("certificates[{index}]"),
+                        "warning" => e,
+                    );
+                    continue;
+                }
+            };
+            unused_chain_certificates.retain(|i| !path.chain_indexes.contains(i));
+            info!(
+                LOG_DRAIN,
+                "certificate chain path";
+                "signature" => self.to_string(),
+                "certificate" => format!("certificates[{index}]"),
+                "path" => path.subjects.join(" -> "),
+            );
         }
+        for index in unused_chain_certificates {
+            warn!(
+                LOG_DRAIN,
+                "certificate of the chain not used by any certificate";
+                "signature" => self.to_string(),
+                "certificate" => format!("certificateChain[{index}]"),
+            );
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::certificate_chain::tests::{
+        INTERMEDIATE_CERTIFICATE, LEAF_CERTIFICATE, OTHER_ROOT_CERTIFICATE, ROOT_CERTIFICATE,
+    };
+    use super::super::crl::tests::{ALICE_CERTIFICATE, BOB_CERTIFICATE, CA_CERTIFICATE, CA_CRL};
     use super::super::validation_helpers::tests::PEM_DATA;
     use super::*;
     use crate::tests::mock_crypto_sdk;
@@ -107,10 +243,10 @@ mod tests {
     fn check_image() {
         let certificate = Certificate {
             image: "".to_string(),
-            certificates: vec!["a cert".to_string()],
+            certificates: vec!["a cert".into()],
             certificate_chain: None,
             require_rekor_bundle: true,
-            annotations: None,
+            ..Default::default()
         };
 
         let ctx = mock_crypto_sdk::verify_cert_context();
@@ -131,10 +267,10 @@ mod tests {
     fn check_certificate_chain_is_pem() {
         let certificate = Certificate {
             image: "hello".to_string(),
-            certificates: vec!["a cert".to_string()],
-            certificate_chain: Some(vec!["not pem".to_string()]),
+            certificates: vec!["a cert".into()],
+            certificate_chain: Some(vec!["not pem".into()]),
             require_rekor_bundle: true,
-            annotations: None,
+            ..Default::default()
         };
 
         let ctx = mock_crypto_sdk::verify_cert_context();
@@ -155,10 +291,10 @@ mod tests {
     fn check_certificate() {
         let certificate = Certificate {
             image: "hello".to_string(),
-            certificates: vec!["good1".to_string()],
-            certificate_chain: Some(vec![PEM_DATA.to_string()]),
+            certificates: vec!["good1".into()],
+            certificate_chain: Some(vec![PEM_DATA.into()]),
             require_rekor_bundle: true,
-            annotations: None,
+            ..Default::default()
         };
 
         let ctx = mock_crypto_sdk::verify_cert_context();
@@ -182,10 +318,10 @@ mod tests {
     fn check_multiple_certificates() {
         let certificate = Certificate {
             image: "hello".to_string(),
-            certificates: vec!["good1".to_string(), "good2".to_string()],
-            certificate_chain: Some(vec![PEM_DATA.to_string()]),
+            certificates: vec!["good1".into(), "good2".into()],
+            certificate_chain: Some(vec![PEM_DATA.into()]),
             require_rekor_bundle: true,
-            annotations: None,
+            ..Default::default()
         };
 
         let ctx = mock_crypto_sdk::verify_cert_context();
@@ -209,10 +345,10 @@ mod tests {
     fn require_all_certs_to_be_valid() {
         let certificate = Certificate {
             image: "hello".to_string(),
-            certificates: vec!["good1".to_string(), "bad1".to_string()],
-            certificate_chain: Some(vec![PEM_DATA.to_string()]),
+            certificates: vec!["good1".into(), "bad1".into()],
+            certificate_chain: Some(vec![PEM_DATA.into()]),
             require_rekor_bundle: true,
-            annotations: None,
+            ..Default::default()
         };
 
         let ctx = mock_crypto_sdk::verify_cert_context();
@@ -231,4 +367,97 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().as_str(), "not valid");
     }
+
+    #[test]
+    #[serial]
+    fn check_threshold() {
+        let certificate = Certificate {
+            image: "hello".to_string(),
+            certificates: vec!["good1".into(), "good2".into()],
+            require_rekor_bundle: true,
+            threshold: Some(3),
+            ..Default::default()
+        };
+
+        let ctx = mock_crypto_sdk::verify_cert_context();
+        ctx.expect()
+            .times(0)
+            .returning(|_cert, _cert_chain, _not_after| Ok(BoolWithReason::True));
+
+        let result = certificate.validate();
+        assert_eq!(
+            result.unwrap_err().as_str(),
+            "threshold 3 is greater than the number of signers (2)"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn check_revoked_certificates() {
+        let mut certificate = Certificate {
+            image: "hello".to_string(),
+            certificates: vec![ALICE_CERTIFICATE.into(), BOB_CERTIFICATE.into()],
+            certificate_chain: Some(vec![CA_CERTIFICATE.into()]),
+            crls: Some(vec![CA_CRL.to_string()]),
+            ..Default::default()
+        };
+
+        let ctx = mock_crypto_sdk::verify_cert_context();
+        ctx.expect()
+            .times(1)
+            .returning(|_cert, _cert_chain, _not_after| Ok(BoolWithReason::True));
+
+        let error = certificate.validate().unwrap_err();
+        assert!(
+            error.starts_with("certificates[1]: certificate with serial 10:02 revoked on"),
+            "{error}"
+        );
+        assert!(error.ends_with("reason: KeyCompromise"));
+
+        certificate.certificates.pop();
+        assert!(certificate.validate().is_ok());
+
+        certificate.crls = Some(vec!["not a CRL".to_string()]);
+        assert!(certificate
+            .validate()
+            .unwrap_err()
+            .starts_with("crls[0]: neither PEM nor base64 encoded DER"));
+    }
+
+    #[test]
+    #[serial]
+    fn check_certificate_chain_path() {
+        let mut certificate = Certificate {
+            image: "hello".to_string(),
+            certificates: vec![LEAF_CERTIFICATE.into()],
+            certificate_chain: Some(vec![
+                OTHER_ROOT_CERTIFICATE.into(),
+                INTERMEDIATE_CERTIFICATE.into(),
+                ROOT_CERTIFICATE.into(),
+            ]),
+            ..Default::default()
+        };
+
+        let ctx = mock_crypto_sdk::verify_cert_context();
+        ctx.expect()
+            .times(3)
+            .returning(|_cert, _cert_chain, _not_after| Ok(BoolWithReason::True));
+
+        // the unused root is only reported with a warning
+        assert!(certificate.validate().is_ok());
+
+        // the chain can stop at a pinned intermediate, the incomplete path is
+        // only reported with a warning
+        certificate.certificate_chain = Some(vec![INTERMEDIATE_CERTIFICATE.into()]);
+        assert!(certificate.validate().is_ok());
+
+        // the certificates of the chain that are not CAs are only reported
+        // with a warning
+        certificate.certificate_chain = Some(vec![
+            INTERMEDIATE_CERTIFICATE.into(),
+            ROOT_CERTIFICATE.into(),
+            LEAF_CERTIFICATE.into(),
+        ]);
+        assert!(certificate.validate().is_ok());
+    }
 }
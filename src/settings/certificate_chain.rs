@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use x509_parser::prelude::*;
+
+/// Details of a PEM encoded certificate needed to build the chain paths
+struct ChainCertificate {
+    subject: String,
+    raw_subject: Vec<u8>,
+    raw_issuer: Vec<u8>,
+    not_after: Option<DateTime<Utc>>,
+    is_ca: bool,
+}
+
+impl ChainCertificate {
+    fn parse(pem: &str) -> Option<Self> {
+        let (_, pem) = parse_x509_pem(pem.as_bytes()).ok()?;
+        let certificate = pem.parse_x509().ok()?;
+        Some(ChainCertificate {
+            subject: certificate.subject().to_string(),
+            raw_subject: certificate.subject().as_raw().to_vec(),
+            raw_issuer: certificate.issuer().as_raw().to_vec(),
+            not_after: DateTime::from_timestamp(certificate.validity().not_after.timestamp(), 0),
+            is_ca: certificate
+                .basic_constraints()
+                .ok()
+                .flatten()
+                .is_some_and(|constraints| constraints.value.ca),
+        })
+    }
+
+    fn is_self_signed(&self) -> bool {
+        self.raw_subject == self.raw_issuer
+    }
+}
+
+/// Returns the end of the validity of the PEM encoded certificate
+pub(crate) fn certificate_not_after(pem: &str) -> Option<DateTime<Utc>> {
+    ChainCertificate::parse(pem)?.not_after
+}
+
+/// Returns the problems of the certificates of the chain: the certificates
+/// that cannot be parsed, that are not CAs or that have expired. They are only
+/// reported, the host decides whether the chain can be used.
+pub(crate) fn chain_certificate_warnings(chain: &[&str], now: DateTime<Utc>) -> Vec<String> {
+    chain
+        .iter()
+        .enumerate()
+        .filter_map(|(index, pem)| {
+            let warning = match ChainCertificate::parse(pem) {
+                None => "cannot parse the certificate".to_string(),
+                Some(certificate) if !certificate.is_ca => {
+                    format!("{} is not a CA", certificate.subject)
+                }
+                Some(certificate) => match certificate.not_after {
+                    Some(not_after) if not_after < now => format!(
+                        "{} expired on {}",
+                        certificate.subject,
+                        not_after.to_rfc3339()
+                    ),
+                    _ => return None,
+                },
+            };
+            Some(format!("certificateChain[{index}]: {warning}"))
+        })
+        .collect()
+}
+
+/// Path of a certificate to the root of the chain
+#[derive(Debug, PartialEq)]
+pub(crate) struct ChainPath {
+    /// Subjects of the certificate and of its issuers, up to the root
+    pub(crate) subjects: Vec<String>,
+    /// Indexes of the issuers inside of the chain
+    pub(crate) chain_indexes: Vec<usize>,
+}
+
+/// Builds the path from the PEM encoded certificate to the self-signed root
+/// of the chain, following the issuers. Returns `None` when the certificate
+/// cannot be parsed, and an error when an issuer is missing from the chain,
+/// e.g. when the chain stops at a pinned intermediate.
+pub(crate) fn chain_path(pem: &str, chain: &[&str]) -> Option<Result<ChainPath, String>> {
+    let certificate = ChainCertificate::parse(pem)?;
+    let chain: Vec<Option<ChainCertificate>> = chain
+        .iter()
+        .map(|pem| ChainCertificate::parse(pem))
+        .collect();
+
+    let mut path = ChainPath {
+        subjects: vec![certificate.subject.clone()],
+        chain_indexes: vec![],
+    };
+    let mut current = &certificate;
+    while !current.is_self_signed() {
+        let issuer = chain.iter().enumerate().find_map(|(index, issuer)| {
+            issuer
+                .as_ref()
+                .filter(|issuer| {
+                    issuer.raw_subject == current.raw_issuer && !path.chain_indexes.contains(&index)
+                })
+                .map(|issuer| (index, issuer))
+        });
+        let Some((index, issuer)) = issuer else {
+            return Some(Err(format!(
+                "the issuer of {} is missing from the certificateChain, path: {}",
+                current.subject,
+                path.subjects.join(" -> ")
+            )));
+        };
+        path.subjects.push(issuer.subject.clone());
+        path.chain_indexes.push(index);
+        current = issuer;
+    }
+
+    Some(Ok(path))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    pub(crate) const ROOT_CERTIFICATE: &str =
+        include_str!("../../test_data/certificate_chain/root.pem");
+    pub(crate) const INTERMEDIATE_CERTIFICATE: &str =
+        include_str!("../../test_data/certificate_chain/intermediate.pem");
+    pub(crate) const LEAF_CERTIFICATE: &str =
+        include_str!("../../test_data/certificate_chain/leaf.pem");
+    pub(crate) const OTHER_ROOT_CERTIFICATE: &str =
+        include_str!("../../test_data/certificate_chain/other-root.pem");
+
+    fn date(year: i32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn path_building() {
+        let path = chain_path(
+            LEAF_CERTIFICATE,
+            &[
+                OTHER_ROOT_CERTIFICATE,
+                ROOT_CERTIFICATE,
+                INTERMEDIATE_CERTIFICATE,
+            ],
+        )
+        .expect("cannot parse the leaf certificate")
+        .expect("cannot build the path");
+        assert_eq!(
+            path,
+            ChainPath {
+                subjects: vec![
+                    "O=Acme, CN=leaf".to_string(),
+                    "O=Acme, CN=Acme Intermediate CA".to_string(),
+                    "O=Acme, CN=Acme Root CA".to_string(),
+                ],
+                chain_indexes: vec![2, 1],
+            }
+        );
+
+        assert_eq!(
+            chain_path(LEAF_CERTIFICATE, &[ROOT_CERTIFICATE])
+                .unwrap()
+                .unwrap_err(),
+            "the issuer of O=Acme, CN=leaf is missing from the certificateChain, path: O=Acme, CN=leaf"
+        );
+        assert_eq!(chain_path("not a certificate", &[ROOT_CERTIFICATE]), None);
+    }
+
+    #[test]
+    fn chain_certificates_warnings() {
+        assert!(chain_certificate_warnings(
+            &[INTERMEDIATE_CERTIFICATE, ROOT_CERTIFICATE],
+            date(2026)
+        )
+        .is_empty());
+
+        assert_eq!(
+            chain_certificate_warnings(&[ROOT_CERTIFICATE, LEAF_CERTIFICATE], date(2026)),
+            vec!["certificateChain[1]: O=Acme, CN=leaf is not a CA"]
+        );
+
+        let not_after = certificate_not_after(ROOT_CERTIFICATE).expect("no end of validity");
+        assert_eq!(
+            chain_certificate_warnings(&[ROOT_CERTIFICATE], date(2100)),
+            vec![format!(
+                "certificateChain[0]: O=Acme, CN=Acme Root CA expired on {}",
+                not_after.to_rfc3339()
+            )]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use wildmatch::WildMatch;
+
+use crate::cosign::{CertificateIdentity, FULCIO_OID_PREFIX};
+
+/// Friendly names of the Fulcio certificate extensions. When an extension
+/// replaced a deprecated one, the deprecated OID is looked up as a fallback.
+const ALIASES: [(&str, &[&str]); 19] = [
+    (
+        "issuer",
+        &["1.3.6.1.4.1.57264.1.8", "1.3.6.1.4.1.57264.1.1"],
+    ),
+    ("githubWorkflowTrigger", &["1.3.6.1.4.1.57264.1.2"]),
+    ("githubWorkflowSHA", &["1.3.6.1.4.1.57264.1.3"]),
+    ("githubWorkflowName", &["1.3.6.1.4.1.57264.1.4"]),
+    ("githubWorkflowRepository", &["1.3.6.1.4.1.57264.1.5"]),
+    ("githubWorkflowRef", &["1.3.6.1.4.1.57264.1.6"]),
+    ("buildSignerURI", &["1.3.6.1.4.1.57264.1.9"]),
+    ("buildSignerDigest", &["1.3.6.1.4.1.57264.1.10"]),
+    ("runnerEnvironment", &["1.3.6.1.4.1.57264.1.11"]),
+    ("sourceRepositoryURI", &["1.3.6.1.4.1.57264.1.12"]),
+    (
+        "sourceRepositoryDigest",
+        &["1.3.6.1.4.1.57264.1.13", "1.3.6.1.4.1.57264.1.3"],
+    ),
+    (
+        "sourceRepositoryRef",
+        &["1.3.6.1.4.1.57264.1.14", "1.3.6.1.4.1.57264.1.6"],
+    ),
+    ("sourceRepositoryIdentifier", &["1.3.6.1.4.1.57264.1.15"]),
+    ("sourceRepositoryOwnerURI", &["1.3.6.1.4.1.57264.1.16"]),
+    (
+        "sourceRepositoryOwnerIdentifier",
+        &["1.3.6.1.4.1.57264.1.17"],
+    ),
+    ("buildConfigURI", &["1.3.6.1.4.1.57264.1.18"]),
+    ("buildConfigDigest", &["1.3.6.1.4.1.57264.1.19"]),
+    (
+        "buildTrigger",
+        &["1.3.6.1.4.1.57264.1.20", "1.3.6.1.4.1.57264.1.2"],
+    ),
+    ("runInvocationURI", &["1.3.6.1.4.1.57264.1.21"]),
+];
+
+/// Values the Fulcio extensions of the signing certificate must have, by OID
+/// or alias. The values support wildcards.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(transparent)]
+pub(crate) struct CertificateExtensions(BTreeMap<String, String>);
+
+impl CertificateExtensions {
+    /// Returns true when the certificate has all the extensions, with the
+    /// expected values
+    pub(crate) fn matches(&self, identity: &CertificateIdentity) -> bool {
+        self.0.iter().all(|(key, expected)| {
+            extension_oids(key)
+                .and_then(|oids| identity.extension(&oids))
+                .is_some_and(|value| WildMatch::new(expected).matches(value))
+        })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.0.is_empty() {
+            return Err("certificateExtensions must not be empty".to_string());
+        }
+        match self.0.keys().find(|key| extension_oids(key).is_none()) {
+            Some(key) => Err(format!(
+                "certificateExtensions: {key} is neither a Fulcio extension OID nor one of {}",
+                ALIASES.map(|(alias, _)| alias).join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for CertificateExtensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let extensions: Vec<String> = self
+            .0
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        write!(f, "[{}]", extensions.join(", "))
+    }
+}
+
+pub(crate) fn validate_certificate_extensions(
+    certificate_extensions: &Option<CertificateExtensions>,
+) -> Result<(), validator::ValidationError> {
+    match certificate_extensions {
+        Some(certificate_extensions) => certificate_extensions.validate().map_err(|e| {
+            validator::ValidationError::new("certificateExtensions").with_message(e.into())
+        }),
+        None => Ok(()),
+    }
+}
+
+/// OIDs of the extension identified by an alias or an OID
+fn extension_oids(key: &str) -> Option<Vec<&str>> {
+    if let Some((_, oids)) = ALIASES.iter().find(|(alias, _)| *alias == key) {
+        return Some(oids.to_vec());
+    }
+
+    let is_fulcio_oid = key
+        .strip_prefix(FULCIO_OID_PREFIX)
+        .is_some_and(|arc| !arc.is_empty() && arc.split('.').all(|n| n.parse::<u32>().is_ok()));
+    is_fulcio_oid.then(|| vec![key])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn identity() -> CertificateIdentity {
+        CertificateIdentity {
+            issuer: "https://token.actions.githubusercontent.com".to_string(),
+            subject: "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+                .to_string(),
+            extensions: BTreeMap::from([
+                (
+                    "1.3.6.1.4.1.57264.1.8".to_string(),
+                    "https://token.actions.githubusercontent.com".to_string(),
+                ),
+                (
+                    "1.3.6.1.4.1.57264.1.12".to_string(),
+                    "https://github.com/acme/app".to_string(),
+                ),
+                ("1.3.6.1.4.1.57264.1.2".to_string(), "push".to_string()),
+            ]),
+        }
+    }
+
+    fn extensions(extensions: &[(&str, &str)]) -> CertificateExtensions {
+        CertificateExtensions(
+            extensions
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[rstest]
+    #[case::oid(&[("1.3.6.1.4.1.57264.1.12", "https://github.com/acme/app")], true)]
+    #[case::alias(&[("sourceRepositoryURI", "https://github.com/acme/app")], true)]
+    #[case::glob(&[("sourceRepositoryURI", "https://github.com/acme/*")], true)]
+    #[case::deprecated_fallback(&[("buildTrigger", "push")], true)]
+    #[case::other_value(&[("sourceRepositoryURI", "https://github.com/acme/app-fork")], false)]
+    #[case::missing(&[("runnerEnvironment", "github-hosted")], false)]
+    #[case::one_mismatch(
+        &[("issuer", "https://token.actions.githubusercontent.com"), ("buildTrigger", "release")],
+        false
+    )]
+    fn extensions_matching(#[case] expected: &[(&str, &str)], #[case] matches: bool) {
+        assert_eq!(extensions(expected).matches(&identity()), matches);
+    }
+
+    #[test]
+    fn validation() {
+        assert!(extensions(&[("1.3.6.1.4.1.57264.1.99", "value")])
+            .validate()
+            .is_ok());
+        assert!(extensions(&[("sourceRepositoryDigest", "abc")])
+            .validate()
+            .is_ok());
+
+        let error = extensions(&[("2.5.29.17", "value")])
+            .validate()
+            .unwrap_err();
+        assert!(
+            error.starts_with("certificateExtensions: 2.5.29.17 is neither a Fulcio extension OID"),
+            "{error}"
+        );
+        assert!(extensions(&[("sourceRepository", "value")])
+            .validate()
+            .is_err());
+        assert!(extensions(&[]).validate().is_err());
+    }
+}
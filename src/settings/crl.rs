@@ -0,0 +1,354 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::pkcs8::{AssociatedOid, DecodePublicKey};
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::fmt;
+use x509_parser::oid_registry::{
+    OID_PKCS1_SHA256WITHRSA, OID_PKCS1_SHA384WITHRSA, OID_PKCS1_SHA512WITHRSA,
+    OID_SIG_ECDSA_WITH_SHA256, OID_SIG_ECDSA_WITH_SHA384,
+};
+use x509_parser::prelude::*;
+
+/// Result of the lookup of a certificate in the CRLs of its issuer
+#[derive(Debug, PartialEq)]
+pub(crate) enum Revocation {
+    /// The certificate is listed by a CRL of its issuer
+    Revoked {
+        serial: String,
+        date: String,
+        reason: String,
+    },
+    /// A CRL naming the issuer of the certificate cannot be verified, hence
+    /// the certificate cannot be trusted
+    UnverifiedCrl(String),
+}
+
+impl fmt::Display for Revocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revocation::Revoked {
+                serial,
+                date,
+                reason,
+            } => write!(
+                f,
+                "certificate with serial {serial} revoked on {date}, reason: {reason}"
+            ),
+            Revocation::UnverifiedCrl(error) => {
+                write!(f, "cannot check the revocation of the certificate: {error}")
+            }
+        }
+    }
+}
+
+/// Decodes a CRL provided either PEM encoded or as base64 encoded DER
+fn crl_der(crl: &str) -> Result<Vec<u8>, String> {
+    let crl = crl.trim();
+    if crl.starts_with("-----BEGIN") {
+        let pem = ::pem::parse(crl).map_err(|e| format!("invalid PEM: {e}"))?;
+        if pem.tag() != "X509 CRL" {
+            return Err(format!("{} found instead of an X509 CRL", pem.tag()));
+        }
+        return Ok(pem.into_contents());
+    }
+    STANDARD
+        .decode(crl.split_whitespace().collect::<String>())
+        .map_err(|e| format!("neither PEM nor base64 encoded DER: {e}"))
+}
+
+fn parse_crl<T>(
+    crl: &str,
+    with_crl: impl FnOnce(&CertificateRevocationList) -> T,
+) -> Result<T, String> {
+    let der = crl_der(crl)?;
+    let (_, crl) = parse_x509_crl(&der).map_err(|e| format!("invalid CRL: {e}"))?;
+    Ok(with_crl(&crl))
+}
+
+/// Verifies the signature of the CRL with the public key of the certificate
+/// of the chain that issued it
+fn verify_crl(crl: &CertificateRevocationList, chain: &[&str]) -> Result<(), String> {
+    let issuer = chain
+        .iter()
+        .filter_map(|pem| parse_x509_pem(pem.as_bytes()).ok())
+        .find_map(|(_, pem)| {
+            let certificate = pem.parse_x509().ok()?;
+            (certificate.subject().as_raw() == crl.issuer().as_raw())
+                .then(|| certificate.public_key().raw.to_vec())
+        })
+        .ok_or_else(|| {
+            format!(
+                "the issuer {} of the CRL is not in the certificateChain",
+                crl.issuer()
+            )
+        })?;
+
+    let algorithm = &crl.signature_algorithm.algorithm;
+    let message = crl.tbs_cert_list.as_ref();
+    let signature = crl.signature_value.data.as_ref();
+    let verified = if *algorithm == OID_SIG_ECDSA_WITH_SHA256 {
+        verify_ecdsa(&issuer, &Sha256::digest(message), signature)
+    } else if *algorithm == OID_SIG_ECDSA_WITH_SHA384 {
+        verify_ecdsa(&issuer, &Sha384::digest(message), signature)
+    } else if *algorithm == OID_PKCS1_SHA256WITHRSA {
+        verify_rsa::<Sha256>(&issuer, message, signature)
+    } else if *algorithm == OID_PKCS1_SHA384WITHRSA {
+        verify_rsa::<Sha384>(&issuer, message, signature)
+    } else if *algorithm == OID_PKCS1_SHA512WITHRSA {
+        verify_rsa::<Sha512>(&issuer, message, signature)
+    } else {
+        return Err(format!(
+            "unsupported signature algorithm {} of the CRL",
+            algorithm.to_id_string()
+        ));
+    };
+
+    match verified {
+        true => Ok(()),
+        false => Err(format!(
+            "the signature of the CRL is not valid for its issuer {}",
+            crl.issuer()
+        )),
+    }
+}
+
+/// Verifies an ECDSA P-256 or P-384 signature of the digest
+fn verify_ecdsa(public_key: &[u8], digest: &[u8], signature: &[u8]) -> bool {
+    if let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(public_key) {
+        return p256::ecdsa::Signature::from_der(signature)
+            .and_then(|signature| key.verify_prehash(digest, &signature))
+            .is_ok();
+    }
+    if let Ok(key) = p384::ecdsa::VerifyingKey::from_public_key_der(public_key) {
+        return p384::ecdsa::Signature::from_der(signature)
+            .and_then(|signature| key.verify_prehash(digest, &signature))
+            .is_ok();
+    }
+    false
+}
+
+/// Verifies an RSA PKCS#1 v1.5 signature of the message
+fn verify_rsa<D: Digest + AssociatedOid>(
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let Ok(key) = RsaPublicKey::from_public_key_der(public_key) else {
+        return false;
+    };
+    key.verify(Pkcs1v15Sign::new::<D>(), &D::digest(message), signature)
+        .is_ok()
+}
+
+/// Validates the CRLs, and their signature with the certificates of the chain
+/// when they are known. Returns the warnings about the CRLs whose
+/// `nextUpdate` has passed: they are still used, but their issuer may have
+/// revoked more certificates since.
+pub(crate) fn validate_crls(
+    crls: &[String],
+    chain: Option<&[&str]>,
+    now: DateTime<Utc>,
+) -> Result<Vec<String>, String> {
+    let mut warnings: Vec<String> = vec![];
+    for (index, crl) in crls.iter().enumerate() {
+        parse_crl(crl, |crl| {
+            if let Some(chain) = chain {
+                verify_crl(crl, chain)?;
+            }
+            if let Some(next_update) = crl
+                .next_update()
+                .and_then(|next_update| DateTime::from_timestamp(next_update.timestamp(), 0))
+                .filter(|next_update| *next_update < now)
+            {
+                warnings.push(format!(
+                    "crls[{index}]: the CRL of {} is outdated since {}",
+                    crl.issuer(),
+                    next_update.to_rfc3339()
+                ));
+            }
+            Ok(())
+        })
+        .and_then(|result| result)
+        .map_err(|e| format!("crls[{index}]: {e}"))?;
+    }
+    Ok(warnings)
+}
+
+/// Looks for the PEM encoded certificate in the CRLs issued by its issuer.
+/// The CRLs are used only when their signature is verified by a certificate
+/// of the chain.
+pub(crate) fn revocation(certificate: &str, crls: &[String], chain: &[&str]) -> Option<Revocation> {
+    let (_, pem) = parse_x509_pem(certificate.as_bytes()).ok()?;
+    let certificate = pem.parse_x509().ok()?;
+
+    crls.iter().find_map(|crl| {
+        parse_crl(crl, |crl| {
+            if crl.issuer().as_raw() != certificate.issuer().as_raw() {
+                return None;
+            }
+            if let Err(e) = verify_crl(crl, chain) {
+                return Some(Revocation::UnverifiedCrl(e));
+            }
+            crl.iter_revoked_certificates()
+                .find(|revoked| revoked.raw_serial() == certificate.raw_serial())
+                .map(|revoked| Revocation::Revoked {
+                    serial: certificate.raw_serial_as_string(),
+                    date: revoked.revocation_date.to_string(),
+                    reason: revoked
+                        .reason_code()
+                        .map_or(ReasonCode::Unspecified, |(_, reason)| reason)
+                        .to_string(),
+                })
+        })
+        .ok()
+        .flatten()
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    pub(crate) const CA_CRL: &str = include_str!("../../test_data/crl/ca.crl.pem");
+    pub(crate) const CA_CRL_BASE64: &str = include_str!("../../test_data/crl/ca.crl.b64");
+    pub(crate) const CA_CERTIFICATE: &str = include_str!("../../test_data/crl/ca.pem");
+    pub(crate) const ALICE_CERTIFICATE: &str = include_str!("../../test_data/crl/alice.pem");
+    pub(crate) const BOB_CERTIFICATE: &str = include_str!("../../test_data/crl/bob.pem");
+    /// CRL of the CA whose `nextUpdate` is 2021-01-01
+    const EXPIRED_CRL: &str = include_str!("../../test_data/crl/expired.crl.pem");
+    /// CRL with the name of the CA as issuer, signed with another key
+    const FORGED_CRL: &str = include_str!("../../test_data/crl/forged.crl.pem");
+    const RSA_CA_CRL: &str = include_str!("../../test_data/crl/rsa-ca.crl.pem");
+    const RSA_CA_CERTIFICATE: &str = include_str!("../../test_data/crl/rsa-ca.pem");
+
+    fn date(year: i32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn crls_validation() {
+        let crls = [
+            CA_CRL.to_string(),
+            CA_CRL_BASE64.to_string(),
+            RSA_CA_CRL.to_string(),
+        ];
+        assert_eq!(
+            validate_crls(
+                &crls,
+                Some(&[CA_CERTIFICATE, RSA_CA_CERTIFICATE]),
+                date(2026)
+            ),
+            Ok(vec![])
+        );
+
+        assert_eq!(
+            validate_crls(
+                &[CA_CRL.to_string(), CA_CERTIFICATE.to_string()],
+                None,
+                date(2026)
+            )
+            .unwrap_err(),
+            "crls[1]: CERTIFICATE found instead of an X509 CRL"
+        );
+        assert!(validate_crls(&["not a CRL".to_string()], None, date(2026))
+            .unwrap_err()
+            .starts_with("crls[0]: neither PEM nor base64 encoded DER"));
+        assert!(
+            validate_crls(&["bm90IGEgQ1JM".to_string()], None, date(2026))
+                .unwrap_err()
+                .starts_with("crls[0]: invalid CRL")
+        );
+    }
+
+    #[test]
+    fn crls_signature_validation() {
+        assert_eq!(
+            validate_crls(
+                &[FORGED_CRL.to_string()],
+                Some(&[CA_CERTIFICATE]),
+                date(2026)
+            )
+            .unwrap_err(),
+            "crls[0]: the signature of the CRL is not valid for its issuer CN=Acme Signing CA"
+        );
+        assert_eq!(
+            validate_crls(
+                &[CA_CRL.to_string()],
+                Some(&[RSA_CA_CERTIFICATE]),
+                date(2026)
+            )
+            .unwrap_err(),
+            "crls[0]: the issuer CN=Acme Signing CA of the CRL is not in the certificateChain"
+        );
+        // the chain is not known yet, e.g. it is stored in the cluster
+        assert!(validate_crls(&[FORGED_CRL.to_string()], None, date(2026)).is_ok());
+    }
+
+    #[test]
+    fn outdated_crls_validation() {
+        assert_eq!(
+            validate_crls(
+                &[CA_CRL.to_string(), EXPIRED_CRL.to_string()],
+                Some(&[CA_CERTIFICATE]),
+                date(2026)
+            ),
+            Ok(vec![
+                "crls[1]: the CRL of CN=Acme Signing CA is outdated since 2021-01-01T00:00:00+00:00"
+                    .to_string()
+            ])
+        );
+        assert_eq!(
+            validate_crls(&[EXPIRED_CRL.to_string()], None, date(2020)),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn revoked_certificates() {
+        for crl in [CA_CRL, CA_CRL_BASE64] {
+            let crls = [crl.to_string()];
+            assert_eq!(
+                revocation(ALICE_CERTIFICATE, &crls, &[CA_CERTIFICATE]),
+                None
+            );
+
+            let Some(Revocation::Revoked { serial, reason, .. }) =
+                revocation(BOB_CERTIFICATE, &crls, &[CA_CERTIFICATE])
+            else {
+                panic!("bob is not revoked");
+            };
+            assert_eq!(serial, "10:02");
+            assert_eq!(reason, "KeyCompromise");
+        }
+
+        // the CRL of another issuer does not revoke the certificate
+        assert_eq!(
+            revocation(CA_CERTIFICATE, &[CA_CRL.to_string()], &[CA_CERTIFICATE]),
+            None
+        );
+    }
+
+    #[test]
+    fn unverified_crls_reject_the_certificates() {
+        assert_eq!(
+            revocation(
+                ALICE_CERTIFICATE,
+                &[FORGED_CRL.to_string()],
+                &[CA_CERTIFICATE]
+            ),
+            Some(Revocation::UnverifiedCrl(
+                "the signature of the CRL is not valid for its issuer CN=Acme Signing CA"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            revocation(ALICE_CERTIFICATE, &[CA_CRL.to_string()], &[])
+                .unwrap()
+                .to_string(),
+            "cannot check the revocation of the certificate: the issuer CN=Acme Signing CA of the CRL is not in the certificateChain"
+        );
+    }
+}
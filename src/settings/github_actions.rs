@@ -1,31 +1,256 @@
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::fmt;
 use validator::Validate;
+use wildmatch::WildMatch;
 
-#[derive(Serialize, Deserialize, Debug)]
+use super::certificate_extensions::{validate_certificate_extensions, CertificateExtensions};
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+use crate::cosign::{
+    CertificateIdentity, BUILD_TRIGGER_OID, GITHUB_WORKFLOW_TRIGGER_OID, RUNNER_ENVIRONMENT_OID,
+};
+
+const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+const GITHUB_URL: &str = "https://github.com/";
+const WORKFLOWS_DIRECTORY: &str = ".github/workflows/";
+
+/// Kind of runner that executed the GitHub Actions workflow
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum RunnerEnvironment {
+    GithubHosted,
+    SelfHosted,
+}
+
+impl RunnerEnvironment {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RunnerEnvironment::GithubHosted => "github-hosted",
+            RunnerEnvironment::SelfHosted => "self-hosted",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct KeylessGithubActionsInfo {
     /// owner of the repository. E.g: octocat
     pub(crate) owner: String,
     /// Optional - Repo of the GH Action workflow that signed the artifact. E.g: example-repo
     pub(crate) repo: Option<String>,
+    /// Optional - Path of the workflow that signed the artifact. E.g: .github/workflows/release.yml
+    pub(crate) workflow_path: Option<String>,
+    /// Optional - Git refs the workflow must have run for, supports wildcards. E.g: refs/tags/v*
+    pub(crate) refs: Option<Vec<String>>,
+    /// Optional - Events that must have triggered the workflow. E.g: push
+    pub(crate) trigger_events: Option<Vec<String>>,
+    /// Optional - Kind of runner that must have executed the workflow
+    pub(crate) runner_environment: Option<RunnerEnvironment>,
+}
+
+impl KeylessGithubActionsInfo {
+    /// Returns true when the workflow is constrained beyond its owner and
+    /// repository. These constraints are checked against the certificates
+    /// of the signatures.
+    pub(crate) fn has_workflow_constraints(&self) -> bool {
+        self.workflow_path.is_some()
+            || self.refs.is_some()
+            || self.trigger_events.is_some()
+            || self.runner_environment.is_some()
+    }
+
+    /// Returns true when the certificate has been issued to a workflow
+    /// satisfying all the constraints
+    pub(crate) fn matches(&self, identity: &CertificateIdentity) -> bool {
+        if identity.issuer != GITHUB_ACTIONS_ISSUER {
+            return false;
+        }
+        // The subject is the workflow URI, e.g.
+        // https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.0.0
+        let Some((workflow, git_ref)) = identity
+            .subject
+            .strip_prefix(GITHUB_URL)
+            .and_then(|workflow| workflow.split_once('@'))
+        else {
+            return false;
+        };
+        let mut components = workflow.splitn(3, '/');
+        let (Some(owner), Some(repo), Some(workflow_path)) =
+            (components.next(), components.next(), components.next())
+        else {
+            return false;
+        };
+
+        owner == self.owner
+            && self.repo.as_ref().is_none_or(|r| r == repo)
+            && self
+                .workflow_path
+                .as_ref()
+                .is_none_or(|path| path == workflow_path)
+            && self.refs.as_ref().is_none_or(|refs| {
+                refs.iter()
+                    .any(|pattern| WildMatch::new(pattern).matches(git_ref))
+            })
+            && self.trigger_events.as_ref().is_none_or(|events| {
+                identity
+                    .extension(&[BUILD_TRIGGER_OID, GITHUB_WORKFLOW_TRIGGER_OID])
+                    .is_some_and(|event| events.iter().any(|e| e == event))
+            })
+            && self.runner_environment.is_none_or(|runner| {
+                identity.extension(&[RUNNER_ENVIRONMENT_OID]) == Some(runner.as_str())
+            })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(workflow_path) = &self.workflow_path {
+            if !workflow_path.starts_with(WORKFLOWS_DIRECTORY) {
+                return Err(format!(
+                    "workflowPath {workflow_path} must start with {WORKFLOWS_DIRECTORY}"
+                ));
+            }
+        }
+        if self.refs.as_ref().is_some_and(Vec::is_empty) {
+            return Err("refs must not be empty".to_string());
+        }
+        if self.trigger_events.as_ref().is_some_and(Vec::is_empty) {
+            return Err("triggerEvents must not be empty".to_string());
+        }
+        Ok(())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Validate)]
+impl fmt::Display for KeylessGithubActionsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.repo.as_deref().unwrap_or("*"))?;
+        if let Some(workflow_path) = &self.workflow_path {
+            write!(f, ", workflow {workflow_path}")?;
+        }
+        if let Some(refs) = &self.refs {
+            write!(f, ", refs [{}]", refs.join(", "))?;
+        }
+        if let Some(events) = &self.trigger_events {
+            write!(f, ", trigger events [{}]", events.join(", "))?;
+        }
+        if let Some(runner) = &self.runner_environment {
+            write!(f, ", runner environment {}", runner.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_github_actions_image"))]
 pub(crate) struct GithubActions {
     /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
-    #[validate(length(min = 1))]
+    #[serde(default)]
     pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
     /// GitHub Actions information that must be found in the signature
     pub(crate) github_actions: KeylessGithubActionsInfo,
-    /// Optional - Annotations that must have been provided by all signers when they signed the OCI artifact
-    pub(crate) annotations: Option<BTreeMap<String, String>>,
+    /// Optional - Values the Fulcio extensions of the signing certificate must have, by OID or alias
+    pub(crate) certificate_extensions: Option<CertificateExtensions>,
+}
+
+fn validate_github_actions_image(
+    githubactions: &GithubActions,
+) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&githubactions.image, &githubactions.image_regex)?;
+    validate_certificate_extensions(&githubactions.certificate_extensions)?;
+    githubactions.github_actions.validate().map_err(|e| {
+        validator::ValidationError::new("githubActions")
+            .with_message(format!("githubActions: {e}").into())
+    })
 }
 
 impl fmt::Display for GithubActions {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "GitHub action signature for image {}", self.image)
+        write!(
+            f,
+            "GitHub action signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    fn release_identity() -> CertificateIdentity {
+        CertificateIdentity {
+            issuer: GITHUB_ACTIONS_ISSUER.to_string(),
+            subject: "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+                .to_string(),
+            extensions: BTreeMap::from([
+                (BUILD_TRIGGER_OID.to_string(), "push".to_string()),
+                (
+                    RUNNER_ENVIRONMENT_OID.to_string(),
+                    "github-hosted".to_string(),
+                ),
+            ]),
+        }
+    }
+
+    fn workflow() -> KeylessGithubActionsInfo {
+        KeylessGithubActionsInfo {
+            owner: "acme".to_string(),
+            repo: Some("app".to_string()),
+            workflow_path: Some(".github/workflows/release.yml".to_string()),
+            refs: Some(vec!["refs/tags/v*".to_string()]),
+            trigger_events: Some(vec!["push".to_string(), "release".to_string()]),
+            runner_environment: Some(RunnerEnvironment::GithubHosted),
+        }
+    }
+
+    #[rstest]
+    #[case::all_constraints(workflow(), true)]
+    #[case::owner_only(KeylessGithubActionsInfo { owner: "acme".to_string(), ..Default::default() }, true)]
+    #[case::other_owner(KeylessGithubActionsInfo { owner: "acme-fork".to_string(), ..workflow() }, false)]
+    #[case::other_workflow(KeylessGithubActionsInfo { workflow_path: Some(".github/workflows/ci.yml".to_string()), ..workflow() }, false)]
+    #[case::branch_only(KeylessGithubActionsInfo { refs: Some(vec!["refs/heads/main".to_string()]), ..workflow() }, false)]
+    #[case::pull_request_only(KeylessGithubActionsInfo { trigger_events: Some(vec!["pull_request".to_string()]), ..workflow() }, false)]
+    #[case::self_hosted(KeylessGithubActionsInfo { runner_environment: Some(RunnerEnvironment::SelfHosted), ..workflow() }, false)]
+    fn workflow_matching(#[case] github_actions: KeylessGithubActionsInfo, #[case] expected: bool) {
+        assert_eq!(github_actions.matches(&release_identity()), expected);
+    }
+
+    #[test]
+    fn workflow_matching_requires_github_issuer() {
+        let identity = CertificateIdentity {
+            issuer: "https://accounts.google.com".to_string(),
+            ..release_identity()
+        };
+        assert!(!workflow().matches(&identity));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(workflow().validate().is_ok());
+
+        let github_actions = KeylessGithubActionsInfo {
+            workflow_path: Some("release.yml".to_string()),
+            ..workflow()
+        };
+        assert_eq!(
+            github_actions.validate().unwrap_err(),
+            "workflowPath release.yml must start with .github/workflows/"
+        );
+
+        let github_actions = KeylessGithubActionsInfo {
+            trigger_events: Some(vec![]),
+            ..workflow()
+        };
+        assert_eq!(
+            github_actions.validate().unwrap_err(),
+            "triggerEvents must not be empty"
+        );
     }
 }
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::Validate;
+use wildmatch::WildMatch;
+
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+use crate::cosign::CertificateIdentity;
+
+const GITLAB_ISSUER: &str = "https://gitlab.com";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct KeylessGitlabCiInfo {
+    /// Namespace of the project, including its subgroups. E.g: acme/platform
+    pub(crate) namespace: String,
+    /// Optional - Project of the pipeline that signed the artifact. E.g: app
+    pub(crate) project: Option<String>,
+    /// Optional - Git refs the pipeline must have run for, supports wildcards. E.g: refs/tags/v*
+    pub(crate) refs: Option<Vec<String>>,
+    /// Optional - URL of the GitLab instance issuing the identity tokens, `https://gitlab.com` by default
+    pub(crate) issuer: Option<String>,
+}
+
+impl KeylessGitlabCiInfo {
+    fn issuer(&self) -> &str {
+        self.issuer
+            .as_deref()
+            .unwrap_or(GITLAB_ISSUER)
+            .trim_end_matches('/')
+    }
+
+    /// Returns true when the certificate has been issued to a pipeline
+    /// satisfying all the constraints
+    pub(crate) fn matches(&self, identity: &CertificateIdentity) -> bool {
+        let issuer = self.issuer();
+        if identity.issuer.trim_end_matches('/') != issuer {
+            return false;
+        }
+        // The subject is the URI of the CI configuration, e.g.
+        // https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/tags/v1.0.0
+        let Some((config, git_ref)) = identity
+            .subject
+            .strip_prefix(issuer)
+            .and_then(|subject| subject.strip_prefix('/'))
+            .and_then(|subject| subject.split_once('@'))
+        else {
+            return false;
+        };
+        let Some((project_path, _)) = config.split_once("//") else {
+            return false;
+        };
+
+        let namespace = self.namespace.trim_matches('/');
+        let project_matches = match &self.project {
+            Some(project) => project_path == format!("{namespace}/{project}"),
+            None => project_path.starts_with(&format!("{namespace}/")),
+        };
+
+        project_matches
+            && self.refs.as_ref().is_none_or(|refs| {
+                refs.iter()
+                    .any(|pattern| WildMatch::new(pattern).matches(git_ref))
+            })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.namespace.trim_matches('/').is_empty() {
+            return Err("no namespace provided".to_string());
+        }
+        if self
+            .project
+            .as_ref()
+            .is_some_and(|p| p.is_empty() || p.contains('/'))
+        {
+            return Err("project must be a project name, without its namespace".to_string());
+        }
+        if self.refs.as_ref().is_some_and(Vec::is_empty) {
+            return Err("refs must not be empty".to_string());
+        }
+        if let Some(issuer) = &self.issuer {
+            if !issuer.starts_with("https://") {
+                return Err(format!("issuer {issuer} must be an https URL"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for KeylessGitlabCiInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.namespace.trim_matches('/'),
+            self.project.as_deref().unwrap_or("*")
+        )?;
+        if let Some(refs) = &self.refs {
+            write!(f, ", refs [{}]", refs.join(", "))?;
+        }
+        write!(f, " ({})", self.issuer())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
+#[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_gitlab_ci"))]
+pub(crate) struct GitlabCi {
+    /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+    #[serde(default)]
+    pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    /// GitLab CI information that must be found in the signature
+    pub(crate) gitlab_ci: KeylessGitlabCiInfo,
+}
+
+fn validate_gitlab_ci(gitlab_ci: &GitlabCi) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&gitlab_ci.image, &gitlab_ci.image_regex)?;
+    gitlab_ci.gitlab_ci.validate().map_err(|e| {
+        validator::ValidationError::new("gitlabCi").with_message(format!("gitlabCi: {e}").into())
+    })
+}
+
+impl fmt::Display for GitlabCi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GitLab CI signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn identity(issuer: &str, subject: &str) -> CertificateIdentity {
+        CertificateIdentity {
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn pipeline() -> KeylessGitlabCiInfo {
+        KeylessGitlabCiInfo {
+            namespace: "acme/platform".to_string(),
+            project: Some("app".to_string()),
+            refs: Some(vec!["refs/tags/v*".to_string()]),
+            issuer: None,
+        }
+    }
+
+    #[rstest]
+    #[case::tag(
+        GITLAB_ISSUER,
+        "https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/tags/v2.0.0",
+        true
+    )]
+    #[case::branch(
+        GITLAB_ISSUER,
+        "https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/heads/main",
+        false
+    )]
+    #[case::other_project(
+        GITLAB_ISSUER,
+        "https://gitlab.com/acme/platform/app-fork//.gitlab-ci.yml@refs/tags/v2.0.0",
+        false
+    )]
+    #[case::subgroup_project(
+        GITLAB_ISSUER,
+        "https://gitlab.com/acme/platform/app/tools//.gitlab-ci.yml@refs/tags/v2.0.0",
+        false
+    )]
+    #[case::other_issuer(
+        "https://gitlab.acme.org",
+        "https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/tags/v2.0.0",
+        false
+    )]
+    fn pipeline_matching(#[case] issuer: &str, #[case] subject: &str, #[case] expected: bool) {
+        assert_eq!(pipeline().matches(&identity(issuer, subject)), expected);
+    }
+
+    #[test]
+    fn pipeline_matching_on_self_managed_instance() {
+        let gitlab_ci = KeylessGitlabCiInfo {
+            namespace: "acme".to_string(),
+            issuer: Some("https://gitlab.acme.org/".to_string()),
+            ..Default::default()
+        };
+
+        assert!(gitlab_ci.matches(&identity(
+            "https://gitlab.acme.org",
+            "https://gitlab.acme.org/acme/platform/app//ci/build.yml@refs/heads/main"
+        )));
+        assert!(!gitlab_ci.matches(&identity(
+            GITLAB_ISSUER,
+            "https://gitlab.com/acme/platform/app//.gitlab-ci.yml@refs/heads/main"
+        )));
+        assert!(!gitlab_ci.matches(&identity(
+            "https://gitlab.acme.org",
+            "https://gitlab.acme.org/acme-fork/app//.gitlab-ci.yml@refs/heads/main"
+        )));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(pipeline().validate().is_ok());
+
+        let gitlab_ci = KeylessGitlabCiInfo {
+            project: Some("platform/app".to_string()),
+            ..pipeline()
+        };
+        assert_eq!(
+            gitlab_ci.validate().unwrap_err(),
+            "project must be a project name, without its namespace"
+        );
+
+        let gitlab_ci = KeylessGitlabCiInfo {
+            issuer: Some("gitlab.acme.org".to_string()),
+            ..pipeline()
+        };
+        assert_eq!(
+            gitlab_ci.validate().unwrap_err(),
+            "issuer gitlab.acme.org must be an https URL"
+        );
+    }
+}
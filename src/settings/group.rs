@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+use super::Signature;
+
+/// Boolean composition of signature rules
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum GroupRule {
+    /// All the rules must be satisfied
+    AllOf(Vec<Signature>),
+    /// At least one of the rules must be satisfied
+    AnyOf(Vec<Signature>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SignatureGroup {
+    /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`).
+    /// The rules of the group that do not provide an image inherit this one.
+    #[serde(default)]
+    pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`.
+    /// The rules of the group that do not provide an image inherit this one.
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    #[serde(flatten)]
+    pub(crate) rule: GroupRule,
+}
+
+impl fmt::Display for SignatureGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self.rule {
+            GroupRule::AllOf(_) => "allOf",
+            GroupRule::AnyOf(_) => "anyOf",
+        };
+        write!(
+            f,
+            "Signature group {operator} for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
+    }
+}
+
+impl SignatureGroup {
+    pub(crate) fn rules(&self) -> &[Signature] {
+        match &self.rule {
+            GroupRule::AllOf(rules) | GroupRule::AnyOf(rules) => rules,
+        }
+    }
+
+    pub(crate) fn rules_mut(&mut self) -> &mut [Signature] {
+        match &mut self.rule {
+            GroupRule::AllOf(rules) | GroupRule::AnyOf(rules) => rules,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        validate_image_pattern(&self.image, &self.image_regex).map_err(|e| e.to_string())?;
+
+        if self.rules().is_empty() {
+            return Err("no rule provided".to_string());
+        }
+
+        let validation_errors: Vec<String> = self
+            .rules()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, s)| {
+                s.validate()
+                    .err()
+                    .map(|e| format!("rule {index} ({s}): {e}"))
+            })
+            .collect();
+
+        if validation_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validation_errors.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Keyless, PubKeys};
+    use kubewarden::host_capabilities::verification::KeylessInfo;
+
+    #[test]
+    fn deserialize_group_inherits_image() {
+        let signature: Signature = serde_json::from_value(serde_json::json!({
+            "image": "ghcr.io/kubewarden/*",
+            "allOf": [
+                {
+                    "anyOf": [
+                        {
+                            "keyless": [{ "issuer": "issuer", "subject": "subject" }]
+                        },
+                        {
+                            "image": "ghcr.io/kubewarden/policy-server:*",
+                            "pubKeys": ["key"]
+                        }
+                    ]
+                },
+                {
+                    "pubKeys": ["key"]
+                }
+            ]
+        }))
+        .expect("cannot deserialize group");
+
+        let Signature::Group(group) = signature else {
+            panic!("not a group");
+        };
+        let GroupRule::AllOf(rules) = &group.rule else {
+            panic!("not an allOf group");
+        };
+        let Signature::Group(nested) = &rules[0] else {
+            panic!("not a nested group");
+        };
+        assert_eq!(nested.image, "ghcr.io/kubewarden/*");
+        assert_eq!(nested.rules()[0].image(), "ghcr.io/kubewarden/*");
+        assert_eq!(
+            nested.rules()[1].image(),
+            "ghcr.io/kubewarden/policy-server:*"
+        );
+        assert_eq!(rules[1].image(), "ghcr.io/kubewarden/*");
+    }
+
+    #[test]
+    fn deserialize_group_inherits_image_regex() {
+        let signature: Signature = serde_json::from_value(serde_json::json!({
+            "imageRegex": "^ghcr\\.io/kubewarden/",
+            "anyOf": [
+                { "pubKeys": ["key"] },
+                { "image": "ghcr.io/kubewarden/policy-server:*", "pubKeys": ["key"] }
+            ]
+        }))
+        .expect("cannot deserialize group");
+
+        let Signature::Group(group) = signature else {
+            panic!("not a group");
+        };
+        assert!(group.rules()[0].matches_image("ghcr.io/kubewarden/kwctl:v1.0.0"));
+        assert!(!group.rules()[0].matches_image("docker.io/kubewarden/kwctl:v1.0.0"));
+        assert!(group.rules()[1].image_regex().is_none());
+    }
+
+    #[test]
+    fn validation_reports_invalid_rules() {
+        let group = SignatureGroup {
+            image: "ghcr.io/kubewarden/*".to_string(),
+            image_regex: None,
+            scope: Default::default(),
+            rule: GroupRule::AnyOf(vec![
+                Signature::Keyless(Keyless {
+                    image: "ghcr.io/kubewarden/*".to_string(),
+                    keyless: vec![KeylessInfo {
+                        issuer: "issuer".to_string(),
+                        subject: "subject".to_string(),
+                    }],
+                    ..Default::default()
+                }),
+                Signature::PubKeys(PubKeys {
+                    image: "ghcr.io/kubewarden/*".to_string(),
+                    pub_keys: vec![],
+                    ..Default::default()
+                }),
+            ]),
+        };
+
+        let error = group.validate().unwrap_err();
+        assert!(
+            error.starts_with("rule 1 (Pub key signature for image ghcr.io/kubewarden/*)"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn validation_fails_with_empty_group() {
+        let group = SignatureGroup {
+            image: "ghcr.io/kubewarden/*".to_string(),
+            image_regex: None,
+            scope: Default::default(),
+            rule: GroupRule::AllOf(vec![]),
+        };
+
+        assert_eq!(group.validate().unwrap_err(), "no rule provided");
+    }
+
+    #[test]
+    fn validation_rejects_group_annotations() {
+        let signature: Signature = serde_json::from_value(serde_json::json!({
+            "image": "ghcr.io/kubewarden/*",
+            "annotations": { "env": "prod" },
+            "anyOf": [{ "keyless": [{ "issuer": "issuer", "subject": "subject" }] }]
+        }))
+        .expect("cannot deserialize group");
+
+        assert_eq!(
+            signature.validate().unwrap_err(),
+            "annotations must be set on the rules of the group"
+        );
+    }
+}
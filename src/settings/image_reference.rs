@@ -0,0 +1,157 @@
+use wildmatch::WildMatch;
+
+const DEFAULT_REGISTRY: &str = "docker.io";
+const LEGACY_DEFAULT_REGISTRY: &str = "index.docker.io";
+const OFFICIAL_REPOSITORIES_NAMESPACE: &str = "library/";
+const DEFAULT_TAG: &str = ":latest";
+
+fn has_wildcard(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Splits the registry host from the rest of the reference. Like Docker, the
+/// first component is a registry only when it is followed by a path and it
+/// looks like a host name.
+fn split_registry(reference: &str) -> (Option<&str>, &str) {
+    match reference.split_once('/') {
+        Some((first, rest))
+            if first.contains(['.', ':'])
+                || first == "localhost"
+                || first.chars().any(|c| c.is_ascii_uppercase()) =>
+        {
+            (Some(first), rest)
+        }
+        _ => (None, reference),
+    }
+}
+
+fn normalize_registry(registry: &str) -> String {
+    let registry = registry.to_lowercase();
+    if registry == LEGACY_DEFAULT_REGISTRY {
+        DEFAULT_REGISTRY.to_string()
+    } else {
+        registry
+    }
+}
+
+/// Returns true when the reference has a tag or a digest
+fn has_tag_or_digest(repository: &str) -> bool {
+    let last_component = repository.rsplit('/').next().unwrap_or_default();
+    repository.contains('@') || last_component.contains(':')
+}
+
+/// Returns true when the image does not name its registry, e.g. `nginx`
+/// or `acme/app:1.0`
+pub(crate) fn is_short_name(image: &str) -> bool {
+    split_registry(image).0.is_none()
+}
+
+/// Normalizes the image: the registry defaults to `docker.io` and is
+/// lowercased, the official images get the `library/` namespace and the tag
+/// defaults to `latest`
+pub(crate) fn normalize_image(image: &str) -> String {
+    let (registry, repository) = split_registry(image);
+    let registry = registry
+        .map(normalize_registry)
+        .unwrap_or_else(|| DEFAULT_REGISTRY.to_string());
+
+    let mut normalized = format!("{registry}/");
+    if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+        normalized.push_str(OFFICIAL_REPOSITORIES_NAMESPACE);
+    }
+    normalized.push_str(repository);
+    if !has_tag_or_digest(repository) {
+        normalized.push_str(DEFAULT_TAG);
+    }
+    normalized
+}
+
+/// Normalizes the wildcard pattern like `normalize_image`, leaving untouched
+/// the parts where a wildcard could already match the defaults. For example
+/// `*` and `ghcr.io/acme/*` are not changed, while `nginx:*` becomes
+/// `docker.io/library/nginx:*`.
+pub(crate) fn normalize_image_pattern(pattern: &str) -> String {
+    let (registry, repository) = split_registry(pattern);
+    let first_component = repository.split(['/', ':', '@']).next().unwrap_or_default();
+    if registry.is_none() && has_wildcard(first_component) {
+        return pattern.to_string();
+    }
+
+    let registry = registry
+        .map(normalize_registry)
+        .unwrap_or_else(|| DEFAULT_REGISTRY.to_string());
+
+    let mut normalized = format!("{registry}/");
+    let name = repository.split([':', '@']).next().unwrap_or_default();
+    if registry == DEFAULT_REGISTRY && !name.contains('/') && !has_wildcard(name) {
+        normalized.push_str(OFFICIAL_REPOSITORIES_NAMESPACE);
+    }
+    normalized.push_str(repository);
+    if !has_tag_or_digest(repository) && !has_wildcard(repository) {
+        normalized.push_str(DEFAULT_TAG);
+    }
+    normalized
+}
+
+/// Returns true when the wildcard pattern matches the image as written, or
+/// when their normalized forms match
+pub(crate) fn image_pattern_matches(pattern: &str, image: &str) -> bool {
+    WildMatch::new(pattern).matches(image)
+        || WildMatch::new(&normalize_image_pattern(pattern)).matches(&normalize_image(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("nginx", "docker.io/library/nginx:latest")]
+    #[case("library/nginx:1.25", "docker.io/library/nginx:1.25")]
+    #[case("acme/app", "docker.io/acme/app:latest")]
+    #[case("index.docker.io/nginx", "docker.io/library/nginx:latest")]
+    #[case("GHCR.io/acme/app:v1", "ghcr.io/acme/app:v1")]
+    #[case("localhost:5000/app", "localhost:5000/app:latest")]
+    #[case(
+        "nginx@sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e",
+        "docker.io/library/nginx@sha256:89102e348749bb17a6a651a4b2a17420e1a66d2a44a675b981973d49a5af3a5e"
+    )]
+    fn image_normalization(#[case] image: &str, #[case] expected: &str) {
+        assert_eq!(normalize_image(image), expected);
+    }
+
+    #[rstest]
+    #[case("*", "*")]
+    #[case("nginx*", "nginx*")]
+    #[case("*/nginx:*", "*/nginx:*")]
+    #[case("nginx:*", "docker.io/library/nginx:*")]
+    #[case("docker.io/*", "docker.io/*")]
+    #[case("acme/*", "docker.io/acme/*")]
+    #[case("ghcr.io/acme/*", "ghcr.io/acme/*")]
+    #[case("GHCR.io/acme/app", "ghcr.io/acme/app:latest")]
+    fn pattern_normalization(#[case] pattern: &str, #[case] expected: &str) {
+        assert_eq!(normalize_image_pattern(pattern), expected);
+    }
+
+    #[rstest]
+    #[case("docker.io/library/nginx:*", "nginx", true)]
+    #[case("docker.io/library/nginx:*", "library/nginx:1.25", true)]
+    #[case("nginx:1.*", "docker.io/library/nginx:1.25", true)]
+    #[case("ghcr.io/acme/*", "GHCR.IO/acme/app:v1", true)]
+    #[case("nginx*", "nginx:1.25", true)]
+    #[case("*", "nginx", true)]
+    #[case("docker.io/library/nginx:*", "ghcr.io/library/nginx:1.25", false)]
+    #[case("docker.io/library/nginx:1.*", "nginx", false)]
+    fn pattern_matching(#[case] pattern: &str, #[case] image: &str, #[case] expected: bool) {
+        assert_eq!(image_pattern_matches(pattern, image), expected);
+    }
+
+    #[rstest]
+    #[case("nginx", true)]
+    #[case("acme/app:1.0", true)]
+    #[case("docker.io/library/nginx", false)]
+    #[case("localhost/app", false)]
+    fn short_names(#[case] image: &str, #[case] expected: bool) {
+        assert_eq!(is_short_name(image), expected);
+    }
+}
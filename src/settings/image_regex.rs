@@ -0,0 +1,101 @@
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use validator::ValidationError;
+
+/// Regular expression matched against the container images. It is compiled
+/// when the settings are loaded, hence an invalid expression is reported as
+/// a settings error.
+#[derive(Clone, Debug)]
+pub(crate) struct ImageRegex(Regex);
+
+impl ImageRegex {
+    pub(crate) fn is_match(&self, image: &str) -> bool {
+        self.0.is_match(image)
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Serialize for ImageRegex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ImageRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(ImageRegex)
+            .map_err(|e| de::Error::custom(format!("invalid imageRegex {pattern}: {e}")))
+    }
+}
+
+impl TryFrom<&str> for ImageRegex {
+    type Error = regex::Error;
+
+    fn try_from(pattern: &str) -> Result<Self, Self::Error> {
+        Regex::new(pattern).map(ImageRegex)
+    }
+}
+
+/// Pattern of the rule, used to describe it
+pub(crate) fn image_pattern<'a>(image: &'a str, image_regex: &'a Option<ImageRegex>) -> &'a str {
+    match image_regex {
+        Some(image_regex) => image_regex.as_str(),
+        None => image,
+    }
+}
+
+/// Exactly one of `image` and `imageRegex` must be provided
+pub(crate) fn validate_image_pattern(
+    image: &str,
+    image_regex: &Option<ImageRegex>,
+) -> Result<(), ValidationError> {
+    let message = match (image.is_empty(), image_regex) {
+        (true, None) => "no image provided",
+        (false, Some(_)) => "image and imageRegex cannot be set at the same time",
+        _ => return Ok(()),
+    };
+    Err(ValidationError::new("image").with_message(message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_compiles_the_regex() {
+        let image_regex: ImageRegex =
+            serde_json::from_value(serde_json::json!(r"^ghcr\.io/acme/app:v\d+\.\d+\.\d+$"))
+                .expect("cannot deserialize the regex");
+        assert!(image_regex.is_match("ghcr.io/acme/app:v1.2.3"));
+        assert!(!image_regex.is_match("ghcr.io/acme/app:latest"));
+
+        let error = serde_json::from_value::<ImageRegex>(serde_json::json!("ghcr.io/(acme"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("invalid imageRegex ghcr.io/(acme: regex parse error"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn image_pattern_validation() {
+        let image_regex = Some(ImageRegex::try_from("^ghcr\\.io/").unwrap());
+
+        assert!(validate_image_pattern("ghcr.io/*", &None).is_ok());
+        assert!(validate_image_pattern("", &image_regex).is_ok());
+        assert!(validate_image_pattern("", &None).is_err());
+        assert!(validate_image_pattern("ghcr.io/*", &image_regex).is_err());
+    }
+}
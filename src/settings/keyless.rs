@@ -1,20 +1,74 @@
 use kubewarden::host_capabilities::verification::KeylessInfo;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::fmt;
 use validator::Validate;
 
-#[derive(Serialize, Deserialize, Debug, Validate)]
+use super::certificate_extensions::{validate_certificate_extensions, CertificateExtensions};
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+use super::threshold::{
+    required_signers, threshold_validation_error, validate_distinct_signers, validate_threshold,
+};
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_keyless_threshold"))]
+#[validate(schema(function = "validate_keyless_image"))]
 pub(crate) struct Keyless {
-    #[validate(length(min = 1))]
+    #[serde(default)]
     pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
     pub(crate) keyless: Vec<KeylessInfo>,
-    pub(crate) annotations: Option<BTreeMap<String, String>>,
+    /// Optional - Values the Fulcio extensions of the signing certificate must have, by OID or alias
+    pub(crate) certificate_extensions: Option<CertificateExtensions>,
+    /// Optional - Minimum number of keyless identities that must have signed
+    /// the image. When not specified, all the identities are required
+    pub(crate) threshold: Option<usize>,
+    /// Optional - Accept the image when it has been signed by any of the identities
+    #[serde(default)]
+    pub(crate) any_of_signers: bool,
+}
+
+impl Keyless {
+    /// Number of identities that must have signed the image, `None` when all of them are required
+    pub(crate) fn required_signers(&self) -> Option<usize> {
+        required_signers(self.threshold, self.any_of_signers)
+    }
+}
+
+fn validate_keyless_threshold(keyless: &Keyless) -> Result<(), validator::ValidationError> {
+    let identities = keyless
+        .keyless
+        .iter()
+        .map(|keyless| (&keyless.issuer, &keyless.subject))
+        .enumerate();
+    validate_distinct_signers("keyless", identities)
+        .and_then(|_| {
+            validate_threshold(
+                keyless.threshold,
+                keyless.any_of_signers,
+                keyless.keyless.len(),
+            )
+        })
+        .map_err(threshold_validation_error)
+}
+
+fn validate_keyless_image(keyless: &Keyless) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&keyless.image, &keyless.image_regex)?;
+    validate_certificate_extensions(&keyless.certificate_extensions)
 }
 
 impl fmt::Display for Keyless {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Keyless signature for image {}", self.image)
+        write!(
+            f,
+            "Keyless signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
     }
 }
@@ -0,0 +1,291 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::Validate;
+use wildmatch::WildMatch;
+
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+use crate::cosign::CertificateIdentity;
+
+/// How the issuer and the subject of a keyless identity are matched
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum MatchType {
+    /// Regular expressions
+    #[default]
+    Regex,
+    /// Wildcard patterns, supporting `*` and `?`
+    Glob,
+}
+
+/// Pattern compiled when the settings are loaded. Like the
+/// `--certificate-identity-regexp` of cosign, the regular expressions are not
+/// anchored: they match any part of the value unless they use `^` and `$`.
+/// The wildcard patterns match the whole value.
+#[derive(Debug, Clone)]
+enum PatternMatcher {
+    Regex(Regex),
+    Glob(WildMatch),
+}
+
+impl PatternMatcher {
+    fn new(pattern: &str, match_type: MatchType) -> Result<Self, regex::Error> {
+        Ok(match match_type {
+            MatchType::Regex => PatternMatcher::Regex(Regex::new(pattern)?),
+            MatchType::Glob => PatternMatcher::Glob(WildMatch::new(pattern)),
+        })
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            PatternMatcher::Regex(regex) => regex.is_match(value),
+            PatternMatcher::Glob(glob) => glob.matches(value),
+        }
+    }
+}
+
+/// Keyless identity whose issuer and subject are patterns
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(
+    try_from = "KeylessIdentityPatternEntry",
+    into = "KeylessIdentityPatternEntry"
+)]
+pub(crate) struct KeylessIdentityPattern {
+    /// Pattern of the OIDC issuer
+    pub(crate) issuer: String,
+    /// Pattern of the subject, e.g. the email or the workflow URI
+    pub(crate) subject: String,
+    /// How the patterns are matched
+    pub(crate) match_type: MatchType,
+    issuer_matcher: PatternMatcher,
+    subject_matcher: PatternMatcher,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeylessIdentityPatternEntry {
+    issuer: String,
+    subject: String,
+    /// Optional - How the patterns are matched, `regex` by default
+    #[serde(default)]
+    match_type: MatchType,
+}
+
+impl TryFrom<KeylessIdentityPatternEntry> for KeylessIdentityPattern {
+    type Error = String;
+
+    fn try_from(entry: KeylessIdentityPatternEntry) -> Result<Self, Self::Error> {
+        KeylessIdentityPattern::new(&entry.issuer, &entry.subject, entry.match_type)
+    }
+}
+
+impl From<KeylessIdentityPattern> for KeylessIdentityPatternEntry {
+    fn from(pattern: KeylessIdentityPattern) -> Self {
+        KeylessIdentityPatternEntry {
+            issuer: pattern.issuer,
+            subject: pattern.subject,
+            match_type: pattern.match_type,
+        }
+    }
+}
+
+impl fmt::Display for KeylessIdentityPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.subject, self.issuer)
+    }
+}
+
+impl KeylessIdentityPattern {
+    /// Compiles the patterns, an invalid regular expression is an error
+    pub(crate) fn new(issuer: &str, subject: &str, match_type: MatchType) -> Result<Self, String> {
+        let matcher = |field: &str, pattern: &str| {
+            PatternMatcher::new(pattern, match_type)
+                .map_err(|e| format!("invalid {field} regex {pattern}: {e}"))
+        };
+        Ok(KeylessIdentityPattern {
+            issuer: issuer.to_string(),
+            subject: subject.to_string(),
+            match_type,
+            issuer_matcher: matcher("issuer", issuer)?,
+            subject_matcher: matcher("subject", subject)?,
+        })
+    }
+
+    pub(crate) fn matches(&self, identity: &CertificateIdentity) -> bool {
+        self.issuer_matcher.matches(&identity.issuer)
+            && self.subject_matcher.matches(&identity.subject)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (field, pattern) in [("issuer", &self.issuer), ("subject", &self.subject)] {
+            if pattern.is_empty() {
+                return Err(format!("no {field} provided"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
+#[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_keyless_match"))]
+pub(crate) struct KeylessMatch {
+    /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+    #[serde(default)]
+    pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    /// List of keyless identities that must be found
+    #[validate(length(min = 1))]
+    pub(crate) keyless_match: Vec<KeylessIdentityPattern>,
+}
+
+fn validate_keyless_match(keyless_match: &KeylessMatch) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&keyless_match.image, &keyless_match.image_regex)?;
+
+    let errors: Vec<String> = keyless_match
+        .keyless_match
+        .iter()
+        .enumerate()
+        .filter_map(|(index, pattern)| {
+            pattern
+                .validate()
+                .err()
+                .map(|e| format!("keylessMatch[{index}]: {e}"))
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("keylessMatch").with_message(errors.join(", ").into()))
+    }
+}
+
+impl fmt::Display for KeylessMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Keyless signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn identity() -> CertificateIdentity {
+        CertificateIdentity {
+            issuer: "https://token.actions.githubusercontent.com".to_string(),
+            subject: "https://github.com/acme/app/.github/workflows/release.yml@refs/tags/v1.2.0"
+                .to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::regex(
+        r"^https://token\.actions\.githubusercontent\.com$",
+        r"^https://github\.com/acme/[^/]+/\.github/workflows/release\.yml@refs/tags/v\d+",
+        MatchType::Regex,
+        true
+    )]
+    #[case::regex_other_workflow(
+        r"^https://token\.actions\.githubusercontent\.com$",
+        r"^https://github\.com/acme/[^/]+/\.github/workflows/ci\.yml@",
+        MatchType::Regex,
+        false
+    )]
+    #[case::regex_is_not_anchored(
+        r"token\.actions",
+        r"github\.com/acme/app",
+        MatchType::Regex,
+        true
+    )]
+    #[case::regex_anchored_by_the_user(
+        r"^https://token\.actions\.githubusercontent\.com$",
+        r"^https://github\.com/acme/app$",
+        MatchType::Regex,
+        false
+    )]
+    #[case::glob(
+        "https://token.actions.githubusercontent.com",
+        "https://github.com/acme/*/.github/workflows/release.yml@refs/tags/v*",
+        MatchType::Glob,
+        true
+    )]
+    #[case::glob_other_issuer("https://accounts.google.com", "*", MatchType::Glob, false)]
+    fn identity_matching(
+        #[case] issuer: &str,
+        #[case] subject: &str,
+        #[case] match_type: MatchType,
+        #[case] expected: bool,
+    ) {
+        let pattern = KeylessIdentityPattern::new(issuer, subject, match_type)
+            .expect("cannot compile the patterns");
+        assert_eq!(pattern.matches(&identity()), expected);
+    }
+
+    #[test]
+    fn deserialization_compiles_the_patterns() {
+        let pattern: KeylessIdentityPattern = serde_json::from_value(serde_json::json!({
+            "issuer": "https://accounts.google.com",
+            "subject": r"alice@acme\.org"
+        }))
+        .expect("cannot deserialize the pattern");
+        assert_eq!(pattern.match_type, MatchType::Regex);
+        let identity = |subject: &str| CertificateIdentity {
+            issuer: "https://accounts.google.com".to_string(),
+            subject: subject.to_string(),
+            ..Default::default()
+        };
+        assert!(pattern.matches(&identity("alice@acme.org")));
+        assert!(pattern.matches(&identity("alice@acme.org.evil.com")));
+
+        let error = serde_json::from_value::<KeylessIdentityPattern>(serde_json::json!({
+            "issuer": "https://token.actions.githubusercontent.com",
+            "subject": "https://github.com/acme/(app"
+        }))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.starts_with("invalid subject regex https://github.com/acme/(app"),
+            "{error}"
+        );
+
+        assert!(KeylessIdentityPattern::new(
+            "https://token.actions.githubusercontent.com",
+            "https://github.com/acme/(app",
+            MatchType::Glob
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn validation_reports_empty_patterns() {
+        let keyless_match = KeylessMatch {
+            image: "ghcr.io/acme/*".to_string(),
+            keyless_match: vec![KeylessIdentityPattern::new(
+                "https://token.actions.githubusercontent.com",
+                "",
+                MatchType::Glob,
+            )
+            .unwrap()],
+            ..Default::default()
+        };
+
+        let error = keyless_match.validate().unwrap_err().to_string();
+        assert!(
+            error.contains("keylessMatch[0]: no subject provided"),
+            "{error}"
+        );
+    }
+}
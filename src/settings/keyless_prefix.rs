@@ -1,24 +1,45 @@
 use kubewarden::host_capabilities::verification::KeylessPrefixInfo;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::fmt;
 use validator::Validate;
 
-#[derive(Serialize, Deserialize, Debug, Validate)]
+use super::certificate_extensions::{validate_certificate_extensions, CertificateExtensions};
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::rule_scope::RuleScope;
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_keyless_prefix_image"))]
 pub(crate) struct KeylessPrefix {
     /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
-    #[validate(length(min = 1))]
+    #[serde(default)]
     pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
     /// List of keyless signatures that must be found
     #[validate(length(min = 1))]
     pub(crate) keyless_prefix: Vec<KeylessPrefixInfo>,
-    /// Optional - Annotations that must have been provided by all signers when they signed the OCI artifact
-    pub(crate) annotations: Option<BTreeMap<String, String>>,
+    /// Optional - Values the Fulcio extensions of the signing certificate must have, by OID or alias
+    pub(crate) certificate_extensions: Option<CertificateExtensions>,
+}
+
+fn validate_keyless_prefix_image(
+    keylessprefix: &KeylessPrefix,
+) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&keylessprefix.image, &keylessprefix.image_regex)?;
+    validate_certificate_extensions(&keylessprefix.certificate_extensions)
 }
 
 impl fmt::Display for KeylessPrefix {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Keyless signature for image {}", self.image)
+        write!(
+            f,
+            "Keyless signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
     }
 }
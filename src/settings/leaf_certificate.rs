@@ -0,0 +1,325 @@
+use crate::LOG_DRAIN;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use slog::warn;
+use std::fmt;
+use wildmatch::WildMatch;
+use x509_parser::prelude::*;
+
+use super::certificate_chain::chain_certificate_warnings;
+use super::crl::validate_crls;
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::pem_material::{validate_pem_materials, PemMaterial};
+use super::rule_scope::RuleScope;
+
+/// Extended key usage the leaf certificate must allow
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum KeyUsage {
+    ServerAuth,
+    ClientAuth,
+    CodeSigning,
+    EmailProtection,
+    TimeStamping,
+    OcspSigning,
+}
+
+impl fmt::Display for KeyUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usage = match self {
+            KeyUsage::ServerAuth => "serverAuth",
+            KeyUsage::ClientAuth => "clientAuth",
+            KeyUsage::CodeSigning => "codeSigning",
+            KeyUsage::EmailProtection => "emailProtection",
+            KeyUsage::TimeStamping => "timeStamping",
+            KeyUsage::OcspSigning => "ocspSigning",
+        };
+        write!(f, "{usage}")
+    }
+}
+
+impl KeyUsage {
+    /// Returns true when the usage is listed explicitly: `anyExtendedKeyUsage`
+    /// does not satisfy it
+    fn is_allowed(&self, usages: &ExtendedKeyUsage) -> bool {
+        match self {
+            KeyUsage::ServerAuth => usages.server_auth,
+            KeyUsage::ClientAuth => usages.client_auth,
+            KeyUsage::CodeSigning => usages.code_signing,
+            KeyUsage::EmailProtection => usages.email_protection,
+            KeyUsage::TimeStamping => usages.time_stamping,
+            KeyUsage::OcspSigning => usages.ocsp_signing,
+        }
+    }
+}
+
+/// Constraints the leaf certificate of the signatures must satisfy. The
+/// patterns support the `*` and `?` wildcards
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LeafConstraints {
+    /// Optional - Pattern of the subject distinguished name, written like
+    /// `O=Acme, OU=Release Engineering, CN=release-bot`
+    pub(crate) subject: Option<String>,
+    /// Optional - Patterns of the email addresses of the subject alternative
+    /// name, one of them must be matched
+    pub(crate) emails: Option<Vec<String>>,
+    /// Optional - Patterns of the URIs of the subject alternative name, one
+    /// of them must be matched
+    pub(crate) uris: Option<Vec<String>>,
+    /// Optional - Extended key usages the certificate must all allow
+    pub(crate) extended_key_usages: Option<Vec<KeyUsage>>,
+}
+
+impl fmt::Display for LeafConstraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut constraints: Vec<String> = vec![];
+        if let Some(subject) = &self.subject {
+            constraints.push(format!("subject {subject}"));
+        }
+        if let Some(emails) = &self.emails {
+            constraints.push(format!("emails [{}]", emails.join(", ")));
+        }
+        if let Some(uris) = &self.uris {
+            constraints.push(format!("uris [{}]", uris.join(", ")));
+        }
+        if let Some(usages) = &self.extended_key_usages {
+            let usages: Vec<String> = usages.iter().map(KeyUsage::to_string).collect();
+            constraints.push(format!("extended key usages [{}]", usages.join(", ")));
+        }
+        write!(f, "{}", constraints.join(", "))
+    }
+}
+
+impl LeafConstraints {
+    fn is_empty(&self) -> bool {
+        self.subject.is_none()
+            && self.emails.as_ref().is_none_or(Vec::is_empty)
+            && self.uris.as_ref().is_none_or(Vec::is_empty)
+            && self.extended_key_usages.as_ref().is_none_or(Vec::is_empty)
+    }
+
+    /// Returns true when the PEM encoded certificate satisfies all the
+    /// constraints. The certificate is not verified
+    pub(crate) fn matches(&self, certificate: &str) -> bool {
+        let Ok((_, pem)) = parse_x509_pem(certificate.as_bytes()) else {
+            return false;
+        };
+        let Ok(certificate) = pem.parse_x509() else {
+            return false;
+        };
+
+        let matches_any = |patterns: &Option<Vec<String>>, values: &[&str]| {
+            patterns.as_ref().is_none_or(|patterns| {
+                patterns.iter().any(|pattern| {
+                    let pattern = WildMatch::new(pattern);
+                    values.iter().any(|value| pattern.matches(value))
+                })
+            })
+        };
+
+        let names = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|san| san.value.general_names.clone())
+            .unwrap_or_default();
+        let emails: Vec<&str> = names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::RFC822Name(email) => Some(*email),
+                _ => None,
+            })
+            .collect();
+        let uris: Vec<&str> = names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::URI(uri) => Some(*uri),
+                _ => None,
+            })
+            .collect();
+
+        let usages = certificate.extended_key_usage().ok().flatten();
+        let allows_usages = self.extended_key_usages.as_ref().is_none_or(|required| {
+            usages.is_some_and(|usages| required.iter().all(|usage| usage.is_allowed(usages.value)))
+        });
+
+        self.subject.as_ref().is_none_or(|subject| {
+            WildMatch::new(subject).matches(&certificate.subject().to_string())
+        }) && matches_any(&self.emails, &emails)
+            && matches_any(&self.uris, &uris)
+            && allows_usages
+    }
+}
+
+/// Returns the subject distinguished name of the PEM encoded certificate
+pub(crate) fn certificate_subject(certificate: &str) -> Option<String> {
+    let (_, pem) = parse_x509_pem(certificate.as_bytes()).ok()?;
+    let certificate = pem.parse_x509().ok()?;
+    Some(certificate.subject().to_string())
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LeafCertificate {
+    /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+    #[serde(default)]
+    pub(crate) image: String,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    /// The certificate chain of the CA issuing the leaf certificates
+    pub(crate) certificate_chain: Vec<PemMaterial>,
+    /// Constraints the leaf certificate embedded in the signature must satisfy
+    pub(crate) leaf_certificate: LeafConstraints,
+    /// Optional - CRLs, PEM encoded or as base64 encoded DER. The signatures
+    /// of the revoked certificates are rejected
+    pub(crate) crls: Option<Vec<String>>,
+    /// Require the signature layer to have a Rekor bundle, see the
+    /// `certificates` rules
+    pub(crate) require_rekor_bundle: bool,
+}
+
+impl fmt::Display for LeafCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Leaf certificate signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
+    }
+}
+
+impl LeafCertificate {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        validate_image_pattern(&self.image, &self.image_regex).map_err(|e| e.to_string())?;
+
+        if self.certificate_chain.is_empty() {
+            return Err("no certificate chain provided".to_string());
+        }
+        validate_pem_materials(&self.certificate_chain).map_err(|e| e.code.to_string())?;
+        let chain: Vec<&str> = self
+            .certificate_chain
+            .iter()
+            .filter(|c| c.reference.is_none())
+            .map(|c| c.pem.as_str())
+            .collect();
+        for warning in chain_certificate_warnings(&chain, Utc::now()) {
+            warn!(
+                LOG_DRAIN,
+                "invalid certificate of the chain";
+                "signature" => self.to_string(),
+                "warning" => warning,
+            );
+        }
+
+        // Without constraints any certificate issued by the CA, e.g. for a
+        // TLS server, would be accepted
+        if self.leaf_certificate.is_empty() {
+            return Err("no leaf certificate constraint provided".to_string());
+        }
+
+        if let Some(crls) = &self.crls {
+            // the chain stored in the cluster is only read when the requests
+            // are validated
+            let has_chain_references = self.certificate_chain.iter().any(|c| c.reference.is_some());
+            let warnings =
+                validate_crls(crls, (!has_chain_references).then_some(&chain), Utc::now())?;
+            for warning in warnings {
+                warn!(
+                    LOG_DRAIN,
+                    "outdated CRL";
+                    "signature" => self.to_string(),
+                    "warning" => warning,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    pub(crate) const CA_CERTIFICATE: &str = include_str!("../../test_data/leaf_certificate/ca.pem");
+    pub(crate) const RELEASE_CERTIFICATE: &str =
+        include_str!("../../test_data/leaf_certificate/release.pem");
+    pub(crate) const SERVER_CERTIFICATE: &str =
+        include_str!("../../test_data/leaf_certificate/server.pem");
+
+    fn constraints(value: serde_json::Value) -> LeafConstraints {
+        serde_json::from_value(value).expect("cannot deserialize the constraints")
+    }
+
+    #[rstest]
+    #[case::subject(serde_json::json!({ "subject": "O=Acme, OU=Release Engineering, CN=*" }), true)]
+    #[case::other_subject(serde_json::json!({ "subject": "O=Acme, CN=*" }), false)]
+    #[case::email(serde_json::json!({ "emails": ["*@example.com", "*@acme.org"] }), true)]
+    #[case::other_email(serde_json::json!({ "emails": ["*@example.com"] }), false)]
+    #[case::uri(serde_json::json!({ "uris": ["https://build.acme.org/pipelines/*"] }), true)]
+    #[case::code_signing(serde_json::json!({ "extendedKeyUsages": ["codeSigning"] }), true)]
+    #[case::server_auth(
+        serde_json::json!({ "extendedKeyUsages": ["codeSigning", "serverAuth"] }),
+        false
+    )]
+    #[case::all(
+        serde_json::json!({
+            "subject": "*CN=release-bot",
+            "emails": ["release-bot@acme.org"],
+            "uris": ["https://build.acme.org/*"],
+            "extendedKeyUsages": ["codeSigning"]
+        }),
+        true
+    )]
+    fn leaf_matching(#[case] value: serde_json::Value, #[case] expected: bool) {
+        assert_eq!(constraints(value).matches(RELEASE_CERTIFICATE), expected);
+    }
+
+    #[test]
+    fn server_certificate_is_not_a_signer() {
+        let constraints = constraints(serde_json::json!({
+            "subject": "O=Acme, *",
+            "extendedKeyUsages": ["codeSigning"]
+        }));
+        assert!(constraints.matches(RELEASE_CERTIFICATE));
+        assert!(!constraints.matches(SERVER_CERTIFICATE));
+        assert!(!constraints.matches("not a certificate"));
+        assert_eq!(
+            constraints.to_string(),
+            "subject O=Acme, *, extended key usages [codeSigning]"
+        );
+        assert_eq!(
+            certificate_subject(SERVER_CERTIFICATE).as_deref(),
+            Some("O=Acme, CN=www.acme.org")
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let mut leaf_certificate = LeafCertificate {
+            image: "registry.acme.org/*".to_string(),
+            certificate_chain: vec![CA_CERTIFICATE.into()],
+            leaf_certificate: constraints(serde_json::json!({ "emails": [] })),
+            ..Default::default()
+        };
+        assert_eq!(
+            leaf_certificate.validate().unwrap_err(),
+            "no leaf certificate constraint provided"
+        );
+
+        leaf_certificate.leaf_certificate.extended_key_usages = Some(vec![KeyUsage::CodeSigning]);
+        assert!(leaf_certificate.validate().is_ok());
+
+        leaf_certificate.certificate_chain = vec![];
+        assert_eq!(
+            leaf_certificate.validate().unwrap_err(),
+            "no certificate chain provided"
+        );
+    }
+}
@@ -1,11 +1,28 @@
 use crate::LOG_DRAIN;
 
-use serde::{Deserialize, Serialize};
-use slog::info;
+use chrono::{Duration, Utc};
+use k8s_openapi::api::core::v1::Namespace;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use slog::{info, warn};
+use std::collections::BTreeMap;
 use std::fmt;
 use validator::Validate;
+use wildmatch::WildMatch;
 
+mod image_reference;
+pub(crate) use image_reference::is_short_name;
+use image_reference::{image_pattern_matches, normalize_image};
+
+mod image_regex;
+pub(crate) use image_regex::ImageRegex;
+
+mod namespace_selector;
+use namespace_selector::{label_selector_matches, validate_label_selector};
+
+mod threshold;
+use threshold::validate_distinct_signers;
 mod validation_helpers;
+use validation_helpers::validate_exclude_images;
 
 mod pub_keys;
 pub(crate) use pub_keys::PubKeys;
@@ -16,33 +33,488 @@ pub(crate) use keyless::Keyless;
 pub(crate) mod github_actions;
 pub(crate) use github_actions::GithubActions;
 
+pub(crate) mod gitlab_ci;
+pub(crate) use gitlab_ci::GitlabCi;
+
+pub(crate) mod annotations;
+use annotations::{TemplateContext, NAMESPACE_ANNOTATIONS, NAMESPACE_LABELS};
+
 mod certificate;
 pub(crate) use certificate::Certificate;
 
+pub(crate) mod certificate_extensions;
+
+mod certificate_chain;
+use certificate_chain::certificate_not_after;
+
+pub(crate) mod crl;
+
+pub(crate) mod leaf_certificate;
+pub(crate) use leaf_certificate::LeafCertificate;
+
 mod keyless_prefix;
 pub(crate) use keyless_prefix::KeylessPrefix;
 
+pub(crate) mod keyless_match;
+pub(crate) use keyless_match::KeylessMatch;
+
+pub(crate) mod group;
+pub(crate) use group::SignatureGroup;
+
+mod skip_images;
+pub(crate) use skip_images::SkipImage;
+
+mod requesters;
+pub(crate) use requesters::Requester;
+
+mod trust_roots;
+use trust_roots::{resolve_trust_roots, TrustRoot};
+
+pub(crate) mod pem_material;
+use pem_material::{normalize_pem_materials, signer_identities, PemMaterial};
+
+pub(crate) mod public_key;
+use public_key::public_key_algorithm;
+
+pub(crate) mod validity;
+use validity::Validity;
+
+pub(crate) mod rule_scope;
+use rule_scope::RuleScope;
+
 fn default_as_true() -> bool {
     true
 }
 
+fn default_expiration_warning_days() -> u32 {
+    30
+}
+
+fn default_minimum_key_strength() -> u32 {
+    112
+}
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 #[serde(default)]
 #[serde(rename_all = "camelCase")]
+#[serde(remote = "Self")]
 pub(crate) struct Settings {
+    #[serde(deserialize_with = "deserialize_signatures")]
     pub(crate) signatures: Vec<Signature>,
     #[serde(default = "default_as_true")]
     pub(crate) modify_images_with_digest: bool,
+    /// Images exempted from all the signature checks
+    pub(crate) skip_images: Vec<SkipImage>,
+    /// Namespaces where no signature check is performed, supports wildcards
+    pub(crate) excluded_namespaces: Vec<String>,
+    /// Reject the images that do not name their registry, e.g. `nginx`,
+    /// instead of normalizing them
+    pub(crate) reject_short_names: bool,
+    /// What to do with the images that are not matched by any signature rule
+    pub(crate) unmatched_images: UnmatchedImages,
+    /// Requesters that are exempted or that have their own signature rules.
+    /// The first matching entry is used
+    pub(crate) requesters: Vec<Requester>,
+    /// Named sets of keys, certificates or keyless identities that the
+    /// signature rules reference through their `trustRoots` field
+    pub(crate) trust_roots: BTreeMap<String, TrustRoot>,
+    /// Number of days before the end of the validity window of a rule, a key
+    /// or a certificate, or before the expiration of a certificate, from
+    /// which a warning is logged when validating the settings
+    #[serde(default = "default_expiration_warning_days")]
+    pub(crate) expiration_warning_days: u32,
+    /// Minimum bits of security of the public keys, 112 by default, which
+    /// rejects the RSA keys shorter than 2048 bits
+    #[serde(default = "default_minimum_key_strength")]
+    pub(crate) minimum_key_strength: u32,
+}
+
+impl Serialize for Settings {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Settings::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Settings {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut value = serde_json::Value::deserialize(deserializer)?;
+        resolve_trust_roots(&mut value).map_err(de::Error::custom)?;
+        let mut settings = Settings::deserialize(value).map_err(de::Error::custom)?;
+        settings.normalize_pem_materials();
+        Ok(settings)
+    }
+}
+
+/// Handling of the images that are not matched by any signature rule nor
+/// exempted
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum UnmatchedImages {
+    /// The images are accepted
+    #[default]
+    Allow,
+    /// The images are accepted, with a warning listing them
+    Warn,
+    /// The request is rejected
+    Deny,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Settings {
+    /// Returns the active exemption matching the image, if any
+    pub(crate) fn skipped_image(&self, image: &str) -> Option<&SkipImage> {
+        let now = Utc::now();
+        self.skip_images.iter().find(|s| s.exempts(image, now))
+    }
+
+    /// Returns true when the labels of the namespace are required to select the rules
+    pub(crate) fn uses_namespace_selectors(&self) -> bool {
+        self.signatures
+            .iter()
+            .any(Signature::uses_namespace_selector)
+    }
+
+    /// Returns true when the annotation templates use the labels or the
+    /// annotations of the namespace
+    pub(crate) fn uses_namespace_templates(&self) -> bool {
+        fn uses_namespace_metadata(signature: &Signature) -> bool {
+            match signature {
+                Signature::Group(group) => group.rules().iter().any(uses_namespace_metadata),
+                signature => signature
+                    .scope()
+                    .annotations
+                    .as_ref()
+                    .is_some_and(|annotations| {
+                        annotations.template_variables().iter().any(|variable| {
+                            variable.starts_with(NAMESPACE_LABELS)
+                                || variable.starts_with(NAMESPACE_ANNOTATIONS)
+                        })
+                    }),
+            }
+        }
+        self.signatures.iter().any(uses_namespace_metadata)
+    }
+
+    /// Replaces the annotation templates of the signature rules with the
+    /// values of the admission request
+    pub(crate) fn resolve_annotation_templates(
+        &mut self,
+        context: &TemplateContext,
+    ) -> Result<(), String> {
+        fn resolve(signature: &mut Signature, context: &TemplateContext) -> Result<(), String> {
+            if let Signature::Group(group) = signature {
+                return group
+                    .rules_mut()
+                    .iter_mut()
+                    .try_for_each(|rule| resolve(rule, context));
+            }
+            let description = signature.to_string();
+            match signature.scope_mut().annotations.as_mut() {
+                Some(annotations) => annotations
+                    .resolve_templates(context)
+                    .map_err(|e| format!("{description}: {e}")),
+                None => Ok(()),
+            }
+        }
+        self.signatures
+            .iter_mut()
+            .try_for_each(|signature| resolve(signature, context))
+    }
+
+    /// Reads the keys and the certificates stored in ConfigMaps and Secrets.
+    /// Only the references of the rules accepted by `applies` are read, so the
+    /// requests do not fetch the resources of the rules they do not use.
+    pub(crate) fn resolve_pem_references(
+        &mut self,
+        applies: impl Fn(&Signature) -> bool,
+    ) -> Result<(), String> {
+        fn resolve(
+            signature: &mut Signature,
+            minimum_key_strength: u32,
+            applies: &impl Fn(&Signature) -> bool,
+        ) -> Result<(), String> {
+            if !applies(signature) {
+                return Ok(());
+            }
+            if let Signature::Group(group) = signature {
+                return group
+                    .rules_mut()
+                    .iter_mut()
+                    .try_for_each(|rule| resolve(rule, minimum_key_strength, applies));
+            }
+            let description = signature.to_string();
+            for (field, materials) in signature.pem_materials_mut() {
+                for (index, material) in materials.iter_mut().enumerate() {
+                    material
+                        .resolve()
+                        .map_err(|e| format!("{description}: {field}[{index}]: {e}"))?;
+                }
+                normalize_pem_materials(materials);
+                if field == "certificateChain" {
+                    continue;
+                }
+                validate_distinct_signers(field, signer_identities(materials))
+                    .map_err(|e| format!("{description}: {e}"))?;
+                if field != "pubKeys" {
+                    continue;
+                }
+                // the inline keys have been checked with the settings
+                for (index, material) in materials.iter().enumerate() {
+                    if material.reference.is_some() {
+                        public_key_algorithm(&material.pem)
+                            .and_then(|algorithm| algorithm.validate_strength(minimum_key_strength))
+                            .map_err(|e| format!("{description}: {field}[{index}]: {e}"))?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        let minimum_key_strength = self.minimum_key_strength;
+        self.signatures
+            .iter_mut()
+            .try_for_each(|signature| resolve(signature, minimum_key_strength, &applies))
+    }
+
+    /// Splits the PEM bundles and encodes as PEM the base64 encoded DER
+    /// provided inline by the signature rules and the trust roots
+    fn normalize_pem_materials(&mut self) {
+        fn normalize(signatures: &mut [Signature]) {
+            for signature in signatures {
+                if let Signature::Group(group) = signature {
+                    normalize(group.rules_mut());
+                }
+                for (_, materials) in signature.pem_materials_mut() {
+                    normalize_pem_materials(materials);
+                }
+            }
+        }
+
+        normalize(&mut self.signatures);
+        for requester in &mut self.requesters {
+            normalize(requester.signatures.as_deref_mut().unwrap_or_default());
+        }
+        for trust_root in self.trust_roots.values_mut() {
+            trust_root.normalize_pem_materials();
+        }
+    }
+
+    /// Returns all the signature rules, including the ones of the groups and
+    /// of the requesters
+    fn all_signatures(&self) -> Vec<&Signature> {
+        fn collect<'a>(signatures: &'a [Signature], all: &mut Vec<&'a Signature>) {
+            for signature in signatures {
+                all.push(signature);
+                if let Signature::Group(group) = signature {
+                    collect(group.rules(), all);
+                }
+            }
+        }
+
+        let mut all = vec![];
+        collect(&self.signatures, &mut all);
+        for requester in &self.requesters {
+            collect(
+                requester.signatures.as_deref().unwrap_or_default(),
+                &mut all,
+            );
+        }
+        all
+    }
+
+    pub(crate) fn is_namespace_excluded(&self, namespace: &str) -> bool {
+        self.excluded_namespaces
+            .iter()
+            .any(|excluded| WildMatch::new(excluded).matches(namespace))
+    }
+}
+
+#[derive(Serialize, Debug)]
 #[serde(untagged, rename_all = "camelCase")]
 pub(crate) enum Signature {
     PubKeys(PubKeys),
     Keyless(Keyless),
     GithubActions(GithubActions),
+    GitlabCi(GitlabCi),
+    KeylessPrefix(KeylessPrefix),
+    KeylessMatch(KeylessMatch),
+    Certificate(Certificate),
+    LeafCertificate(LeafCertificate),
+    Group(SignatureGroup),
+}
+
+/// Value of the optional `type` field of a signature rule. When provided,
+/// the rule is deserialized straight into the matching variant, which
+/// gives field-level errors instead of the generic untagged enum failure.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+enum SignatureType {
+    PubKeys,
+    Keyless,
+    KeylessPrefix,
+    KeylessMatch,
+    GithubActions,
+    GitlabCi,
+    Certificate,
+    LeafCertificate,
+    Group,
+}
+
+/// Used to deserialize the rules that do not have an explicit `type`,
+/// preserving the behaviour of the policy before the field was introduced
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UntaggedSignature {
+    PubKeys(PubKeys),
+    Keyless(Keyless),
+    GithubActions(GithubActions),
+    GitlabCi(GitlabCi),
     KeylessPrefix(KeylessPrefix),
+    KeylessMatch(KeylessMatch),
     Certificate(Certificate),
+    LeafCertificate(LeafCertificate),
+    Group(SignatureGroup),
+}
+
+impl From<UntaggedSignature> for Signature {
+    fn from(signature: UntaggedSignature) -> Self {
+        match signature {
+            UntaggedSignature::PubKeys(s) => Signature::PubKeys(s),
+            UntaggedSignature::Keyless(s) => Signature::Keyless(s),
+            UntaggedSignature::GithubActions(s) => Signature::GithubActions(s),
+            UntaggedSignature::GitlabCi(s) => Signature::GitlabCi(s),
+            UntaggedSignature::KeylessPrefix(s) => Signature::KeylessPrefix(s),
+            UntaggedSignature::KeylessMatch(s) => Signature::KeylessMatch(s),
+            UntaggedSignature::Certificate(s) => Signature::Certificate(s),
+            UntaggedSignature::LeafCertificate(s) => Signature::LeafCertificate(s),
+            UntaggedSignature::Group(s) => Signature::Group(s),
+        }
+    }
+}
+
+impl Signature {
+    fn from_value(mut value: serde_json::Value) -> Result<Self, String> {
+        let signature_type = match value.as_object_mut() {
+            Some(object) => object.remove("type"),
+            None => return Err("signature must be an object".to_string()),
+        };
+
+        let Some(signature_type) = signature_type else {
+            return serde_json::from_value::<UntaggedSignature>(value)
+                .map(Signature::from)
+                .map(Signature::with_inherited_images)
+                .map_err(|_| {
+                    "data did not match any signature type, set the `type` field to get a detailed error"
+                        .to_string()
+                });
+        };
+
+        let signature_type: SignatureType =
+            serde_json::from_value(signature_type).map_err(|e| format!("invalid type: {e}"))?;
+        let signature = match signature_type {
+            SignatureType::PubKeys => serde_json::from_value(value).map(Signature::PubKeys),
+            SignatureType::Keyless => serde_json::from_value(value).map(Signature::Keyless),
+            SignatureType::KeylessPrefix => {
+                serde_json::from_value(value).map(Signature::KeylessPrefix)
+            }
+            SignatureType::KeylessMatch => {
+                serde_json::from_value(value).map(Signature::KeylessMatch)
+            }
+            SignatureType::GithubActions => {
+                serde_json::from_value(value).map(Signature::GithubActions)
+            }
+            SignatureType::GitlabCi => serde_json::from_value(value).map(Signature::GitlabCi),
+            SignatureType::Certificate => serde_json::from_value(value).map(Signature::Certificate),
+            SignatureType::LeafCertificate => {
+                serde_json::from_value(value).map(Signature::LeafCertificate)
+            }
+            SignatureType::Group => serde_json::from_value(value).map(Signature::Group),
+        };
+        signature
+            .map(Signature::with_inherited_images)
+            .map_err(|e| format!("{signature_type:?}: {e}"))
+    }
+
+    fn with_inherited_images(mut self) -> Self {
+        if let Signature::Group(group) = &self {
+            let (image, image_regex) = (group.image.clone(), group.image_regex.clone());
+            self.inherit_image(&image, &image_regex);
+        }
+        self
+    }
+
+    /// Set the image of the rule when neither `image` nor `imageRegex` have
+    /// been provided, propagating it to the rules of the groups
+    fn inherit_image(&mut self, image: &str, image_regex: &Option<ImageRegex>) {
+        let (own_image, own_image_regex) = match self {
+            Signature::PubKeys(s) => (&mut s.image, &mut s.image_regex),
+            Signature::Keyless(s) => (&mut s.image, &mut s.image_regex),
+            Signature::GithubActions(s) => (&mut s.image, &mut s.image_regex),
+            Signature::GitlabCi(s) => (&mut s.image, &mut s.image_regex),
+            Signature::KeylessPrefix(s) => (&mut s.image, &mut s.image_regex),
+            Signature::KeylessMatch(s) => (&mut s.image, &mut s.image_regex),
+            Signature::Certificate(s) => (&mut s.image, &mut s.image_regex),
+            Signature::LeafCertificate(s) => (&mut s.image, &mut s.image_regex),
+            Signature::Group(s) => (&mut s.image, &mut s.image_regex),
+        };
+        if own_image.is_empty() && own_image_regex.is_none() {
+            *own_image = image.to_string();
+            *own_image_regex = image_regex.clone();
+        }
+
+        if let Signature::Group(group) = self {
+            let (image, image_regex) = (group.image.clone(), group.image_regex.clone());
+            for rule in group.rules_mut() {
+                rule.inherit_image(&image, &image_regex);
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Signature::from_value(value).map_err(de::Error::custom)
+    }
+}
+
+/// Deserialize the list of signatures, reporting the index of the rule
+/// that cannot be parsed
+fn deserialize_signatures<'de, D>(deserializer: D) -> Result<Vec<Signature>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    signatures_from_values(values).map_err(de::Error::custom)
+}
+
+fn deserialize_optional_signatures<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<Signature>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<serde_json::Value>>::deserialize(deserializer)?
+        .map(signatures_from_values)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+fn signatures_from_values(values: Vec<serde_json::Value>) -> Result<Vec<Signature>, String> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            Signature::from_value(value).map_err(|e| format!("signatures[{index}]: {e}"))
+        })
+        .collect()
 }
 
 impl fmt::Display for Signature {
@@ -51,8 +523,12 @@ impl fmt::Display for Signature {
             Signature::PubKeys(pub_keys) => pub_keys.to_string(),
             Signature::Keyless(keyless) => keyless.to_string(),
             Signature::GithubActions(github_action) => github_action.to_string(),
+            Signature::GitlabCi(gitlab_ci) => gitlab_ci.to_string(),
             Signature::KeylessPrefix(keyless_prefix) => keyless_prefix.to_string(),
+            Signature::KeylessMatch(keyless_match) => keyless_match.to_string(),
             Signature::Certificate(cert) => cert.to_string(),
+            Signature::LeafCertificate(leaf_certificate) => leaf_certificate.to_string(),
+            Signature::Group(group) => group.to_string(),
         };
 
         write!(f, "{detailed_display}")
@@ -65,22 +541,208 @@ impl Signature {
             Signature::PubKeys(s) => s.image.as_str(),
             Signature::Keyless(s) => s.image.as_str(),
             Signature::GithubActions(s) => s.image.as_str(),
+            Signature::GitlabCi(s) => s.image.as_str(),
             Signature::KeylessPrefix(s) => s.image.as_str(),
+            Signature::KeylessMatch(s) => s.image.as_str(),
             Signature::Certificate(s) => s.image.as_str(),
+            Signature::LeafCertificate(s) => s.image.as_str(),
+            Signature::Group(s) => s.image.as_str(),
         }
     }
 
+    pub fn image_regex(&self) -> Option<&ImageRegex> {
+        let image_regex = match self {
+            Signature::PubKeys(s) => &s.image_regex,
+            Signature::Keyless(s) => &s.image_regex,
+            Signature::GithubActions(s) => &s.image_regex,
+            Signature::GitlabCi(s) => &s.image_regex,
+            Signature::KeylessPrefix(s) => &s.image_regex,
+            Signature::KeylessMatch(s) => &s.image_regex,
+            Signature::Certificate(s) => &s.image_regex,
+            Signature::LeafCertificate(s) => &s.image_regex,
+            Signature::Group(s) => &s.image_regex,
+        };
+        image_regex.as_ref()
+    }
+
+    /// Returns true when the image is matched by `imageRegex` or, when it is
+    /// not set, by the `image` wildcard pattern. The image is matched both as
+    /// written and normalized, e.g. `nginx` as `docker.io/library/nginx:latest`
+    pub fn matches_image(&self, image: &str) -> bool {
+        match self.image_regex() {
+            Some(image_regex) => {
+                image_regex.is_match(image) || image_regex.is_match(&normalize_image(image))
+            }
+            None => image_pattern_matches(self.image(), image),
+        }
+    }
+
+    /// Returns true when the image is matched by one of the `excludeImages` patterns
+    pub fn excludes_image(&self, image: &str) -> bool {
+        self.scope()
+            .exclude_images
+            .iter()
+            .flatten()
+            .any(|exclude_image| image_pattern_matches(exclude_image, image))
+    }
+
+    /// Where and when the rule is enforced, and the annotations its
+    /// signatures must have
+    pub fn scope(&self) -> &RuleScope {
+        match self {
+            Signature::PubKeys(s) => &s.scope,
+            Signature::Keyless(s) => &s.scope,
+            Signature::GithubActions(s) => &s.scope,
+            Signature::GitlabCi(s) => &s.scope,
+            Signature::KeylessPrefix(s) => &s.scope,
+            Signature::KeylessMatch(s) => &s.scope,
+            Signature::Certificate(s) => &s.scope,
+            Signature::LeafCertificate(s) => &s.scope,
+            Signature::Group(s) => &s.scope,
+        }
+    }
+
+    fn scope_mut(&mut self) -> &mut RuleScope {
+        match self {
+            Signature::PubKeys(s) => &mut s.scope,
+            Signature::Keyless(s) => &mut s.scope,
+            Signature::GithubActions(s) => &mut s.scope,
+            Signature::GitlabCi(s) => &mut s.scope,
+            Signature::KeylessPrefix(s) => &mut s.scope,
+            Signature::KeylessMatch(s) => &mut s.scope,
+            Signature::Certificate(s) => &mut s.scope,
+            Signature::LeafCertificate(s) => &mut s.scope,
+            Signature::Group(s) => &mut s.scope,
+        }
+    }
+
+    /// Keys and certificates of the rule, by field
+    fn pem_materials(&self) -> Vec<(&'static str, &[PemMaterial])> {
+        match self {
+            Signature::PubKeys(s) => vec![("pubKeys", &s.pub_keys)],
+            Signature::Certificate(s) => {
+                let mut materials: Vec<(&'static str, &[PemMaterial])> =
+                    vec![("certificates", &s.certificates)];
+                if let Some(chain) = &s.certificate_chain {
+                    materials.push(("certificateChain", chain));
+                }
+                materials
+            }
+            Signature::LeafCertificate(s) => vec![("certificateChain", &s.certificate_chain)],
+            _ => vec![],
+        }
+    }
+
+    fn pem_materials_mut(&mut self) -> Vec<(&'static str, &mut Vec<PemMaterial>)> {
+        match self {
+            Signature::PubKeys(s) => vec![("pubKeys", &mut s.pub_keys)],
+            Signature::Certificate(s) => {
+                let mut materials: Vec<(&'static str, &mut Vec<PemMaterial>)> =
+                    vec![("certificates", &mut s.certificates)];
+                if let Some(chain) = &mut s.certificate_chain {
+                    materials.push(("certificateChain", chain));
+                }
+                materials
+            }
+            Signature::LeafCertificate(s) => {
+                vec![("certificateChain", &mut s.certificate_chain)]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Validity windows of the rule and of its keys or certificates, along
+    /// with what they bound
+    fn validity_windows(&self) -> Vec<(String, &Validity)> {
+        let mut windows = vec![("rule".to_string(), &self.scope().validity)];
+        for (field, materials) in self.pem_materials() {
+            windows.extend(
+                materials
+                    .iter()
+                    .enumerate()
+                    .map(|(index, material)| (format!("{field}[{index}]"), &material.validity)),
+            );
+        }
+        windows
+            .into_iter()
+            .filter(|(_, validity)| validity.is_set())
+            .collect()
+    }
+
+    /// Returns true when the rule, or one of the rules of its group, selects
+    /// the namespaces by their labels
+    pub fn uses_namespace_selector(&self) -> bool {
+        self.scope().namespace_selector.is_some()
+            || matches!(self, Signature::Group(group) if group.rules().iter().any(Signature::uses_namespace_selector))
+    }
+
+    /// Returns true when the rule is enforced in the namespace
+    pub fn applies_to_namespace(&self, namespace: &Namespace) -> bool {
+        let scope = self.scope();
+        let name = namespace.metadata.name.as_deref().unwrap_or_default();
+        let matches_any = |patterns: &Vec<String>| {
+            patterns
+                .iter()
+                .any(|pattern| WildMatch::new(pattern).matches(name))
+        };
+
+        scope.namespaces.as_ref().is_none_or(matches_any)
+            && !scope.exclude_namespaces.as_ref().is_some_and(matches_any)
+            && scope.namespace_selector.as_ref().is_none_or(|selector| {
+                label_selector_matches(
+                    selector,
+                    namespace
+                        .metadata
+                        .labels
+                        .as_ref()
+                        .unwrap_or(&BTreeMap::new()),
+                )
+            })
+    }
+
     fn validate(&self) -> Result<(), String> {
+        let scope = self.scope();
+        if self.image_regex().is_none() {
+            validate_exclude_images(
+                self.image(),
+                scope.exclude_images.as_deref().unwrap_or_default(),
+            )?;
+        }
+        if let Some(selector) = &scope.namespace_selector {
+            validate_label_selector(selector)?;
+        }
+        if let Some(annotations) = &scope.annotations {
+            if matches!(self, Signature::Group(_)) {
+                return Err("annotations must be set on the rules of the group".to_string());
+            }
+            annotations
+                .validate()
+                .map_err(|e| format!("annotations: {e}"))?;
+        }
+        let now = Utc::now();
+        for (bound, validity) in self.validity_windows() {
+            validity.validate(now).map_err(|e| match bound.as_str() {
+                "rule" => e,
+                bound => format!("{bound}: {e}"),
+            })?;
+        }
+
         match self {
             Signature::PubKeys(pub_keys) => pub_keys.validate().map_err(|e| e.to_string()),
             Signature::Keyless(keyless) => keyless.validate().map_err(|e| e.to_string()),
             Signature::GithubActions(github_actions) => {
                 github_actions.validate().map_err(|e| e.to_string())
             }
+            Signature::GitlabCi(gitlab_ci) => gitlab_ci.validate().map_err(|e| e.to_string()),
             Signature::KeylessPrefix(keyless_prefix) => {
                 keyless_prefix.validate().map_err(|e| e.to_string())
             }
+            Signature::KeylessMatch(keyless_match) => {
+                keyless_match.validate().map_err(|e| e.to_string())
+            }
             Signature::Certificate(cert) => cert.validate(),
+            Signature::LeafCertificate(leaf_certificate) => leaf_certificate.validate(),
+            Signature::Group(group) => group.validate(),
         }
     }
 }
@@ -93,7 +755,7 @@ impl kubewarden::settings::Validatable for Settings {
             return Err("Signatures must not be empty".to_string());
         }
 
-        let validation_errors: Vec<String> = self
+        let mut validation_errors: Vec<String> = self
             .signatures
             .iter()
             .filter_map(|s| -> Option<String> {
@@ -104,6 +766,84 @@ impl kubewarden::settings::Validatable for Settings {
             })
             .collect();
 
+        for (name, trust_root) in &self.trust_roots {
+            if let Err(e) = trust_root.validate() {
+                validation_errors.push(format!("Trust root {name}: {e}"));
+            }
+        }
+
+        for requester in &self.requesters {
+            if let Err(e) = requester.validate() {
+                validation_errors.push(format!("{requester}: {e}"));
+            }
+        }
+
+        let now = Utc::now();
+        for skip_image in &self.skip_images {
+            if let Err(e) = skip_image.validate() {
+                validation_errors.push(format!("{skip_image}: {e}"));
+            } else if skip_image.is_expired(now) {
+                warn!(
+                    LOG_DRAIN,
+                    "skipped image exemption is expired and no longer applies";
+                    "image" => &skip_image.image,
+                    "reason" => &skip_image.reason,
+                    "expires" => skip_image.expires.map(|e| e.to_rfc3339()),
+                );
+            }
+        }
+
+        for signature in self.all_signatures() {
+            let Signature::PubKeys(pub_keys) = signature else {
+                continue;
+            };
+            for (index, key) in pub_keys.pub_keys.iter().enumerate() {
+                if key.reference.is_some() {
+                    continue;
+                }
+                // the keys that cannot be parsed are reported by the rule
+                if let Err(e) = public_key_algorithm(&key.pem).map_or(Ok(()), |algorithm| {
+                    algorithm.validate_strength(self.minimum_key_strength)
+                }) {
+                    validation_errors.push(format!("{signature}: pubKeys[{index}]: {e}"));
+                }
+            }
+        }
+
+        let horizon = now + Duration::days(self.expiration_warning_days.into());
+        for signature in self.all_signatures() {
+            for (bound, validity) in signature.validity_windows() {
+                if validity.expires_before(now, horizon) {
+                    warn!(
+                        LOG_DRAIN,
+                        "validity window ends soon";
+                        "signature" => signature.to_string(),
+                        "bound" => bound,
+                        "not-after" => validity.not_after.map(|d| d.to_rfc3339()),
+                    );
+                }
+            }
+            for (field, materials) in signature.pem_materials() {
+                if field == "pubKeys" {
+                    continue;
+                }
+                for (index, material) in materials.iter().enumerate() {
+                    let Some(not_after) = certificate_not_after(&material.pem) else {
+                        continue;
+                    };
+                    if now < not_after && not_after <= horizon {
+                        warn!(
+                            LOG_DRAIN,
+                            "certificate expires soon";
+                            "signature" => signature.to_string(),
+                            "certificate" => format!("{field}[{index}]"),
+                            "not-after" => not_after.to_rfc3339(),
+                        );
+                    }
+                }
+            }
+        }
+
         if validation_errors.is_empty() {
             Ok(())
         } else {
@@ -114,6 +854,9 @@ impl kubewarden::settings::Validatable for Settings {
 
 #[cfg(test)]
 mod tests {
+    use super::public_key::tests::{
+        P256_PUBLIC_KEY, P384_PUBLIC_KEY, RSA_1024_PUBLIC_KEY, RSA_3072_PUBLIC_KEY,
+    };
     use super::*;
     use crate::tests::mock_crypto_sdk;
 
@@ -132,9 +875,10 @@ mod tests {
                     issuer: "issuer".to_string(),
                     subject: "subject".to_string(),
                 }],
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         assert!(settings.validate().is_ok());
@@ -152,6 +896,7 @@ mod tests {
         let settings = Settings {
             signatures: vec![],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         assert!(settings.validate().is_err());
@@ -171,12 +916,13 @@ mod tests {
         let settings = Settings {
             signatures: vec![Signature::Certificate(Certificate {
                 image: "myimage".to_string(),
-                certificates: vec!["this is not a PEM cert".to_string()],
+                certificates: vec!["this is not a PEM cert".into()],
                 certificate_chain: None,
                 require_rekor_bundle: false,
-                annotations: None,
+                ..Default::default()
             })],
             modify_images_with_digest: true,
+            ..Default::default()
         };
 
         let result = settings.validate();
@@ -187,4 +933,428 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn validate_settings_exclude_images_never_matching() {
+        let settings = Settings {
+            signatures: vec![Signature::Keyless(Keyless {
+                image: "ghcr.io/acme/*".to_string(),
+                scope: RuleScope {
+                    exclude_images: Some(vec!["docker.io/acme/legacy-*".to_string()]),
+                    ..Default::default()
+                },
+                keyless: vec![KeylessInfo {
+                    issuer: "issuer".to_string(),
+                    subject: "subject".to_string(),
+                }],
+                ..Default::default()
+            })],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings.validate().unwrap_err(),
+            "Keyless signature for image ghcr.io/acme/*: \"excluded image docker.io/acme/legacy-* can never match image ghcr.io/acme/*\""
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn validate_settings_validity_windows() {
+        let settings_with = |signature: serde_json::Value| -> Settings {
+            serde_json::from_value(serde_json::json!({ "signatures": [signature] }))
+                .expect("cannot deserialize settings")
+        };
+
+        let settings = settings_with(serde_json::json!({
+            "image": "ghcr.io/acme/*",
+            "pubKeys": [P256_PUBLIC_KEY, { "pem": RSA_3072_PUBLIC_KEY, "notBefore": "2100-01-01T00:00:00Z" }],
+            "notAfter": "2100-06-01T00:00:00Z"
+        }));
+        assert_eq!(settings.expiration_warning_days, 30);
+        assert!(settings.validate().is_ok());
+
+        let settings = settings_with(serde_json::json!({
+            "image": "ghcr.io/acme/*",
+            "pubKeys": [P256_PUBLIC_KEY],
+            "notBefore": "2100-06-01T00:00:00Z",
+            "notAfter": "2100-01-01T00:00:00Z"
+        }));
+        assert_eq!(
+            settings.validate().unwrap_err(),
+            "Pub key signature for image ghcr.io/acme/*: \"notBefore 2100-06-01T00:00:00+00:00 is not before notAfter 2100-01-01T00:00:00+00:00\""
+        );
+
+        let settings = settings_with(serde_json::json!({
+            "image": "ghcr.io/acme/*",
+            "allOf": [{
+                "pubKeys": [P256_PUBLIC_KEY, { "pem": RSA_3072_PUBLIC_KEY, "notAfter": "2020-01-01T00:00:00Z" }]
+            }]
+        }));
+        assert_eq!(
+            settings.validate().unwrap_err(),
+            "Signature group allOf for image ghcr.io/acme/*: \"rule 0 (Pub key signature for image ghcr.io/acme/*): pubKeys[1]: the validity window ended on 2020-01-01T00:00:00+00:00\""
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn validate_settings_skip_images() {
+        let mut settings = Settings {
+            signatures: vec![Signature::Keyless(Keyless {
+                image: "ghcr.io/acme/*".to_string(),
+                keyless: vec![KeylessInfo {
+                    issuer: "issuer".to_string(),
+                    subject: "subject".to_string(),
+                }],
+                ..Default::default()
+            })],
+            skip_images: vec![SkipImage {
+                image: "docker.io/bitnami/*".to_string(),
+                reason: "third-party images".to_string(),
+                expires: Some("2020-01-01T00:00:00Z".parse().unwrap()),
+            }],
+            modify_images_with_digest: true,
+            ..Default::default()
+        };
+
+        // expired exemptions are only reported as warnings
+        assert!(settings.validate().is_ok());
+
+        settings.skip_images[0].reason = "".to_string();
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .starts_with("Skipped image docker.io/bitnami/*: reason"));
+    }
+
+    #[test]
+    fn deserialize_untagged_signatures() {
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "githubActions": { "owner": "kubewarden" }
+                },
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "keyless": [{ "issuer": "issuer", "subject": "subject" }]
+                },
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "keylessMatch": [{ "issuer": "issuer", "subject": "sub*", "matchType": "glob" }]
+                },
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "gitlabCi": { "namespace": "kubewarden", "issuer": "https://gitlab.acme.org" }
+                },
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "certificateChain": ["ca"],
+                    "leafCertificate": { "extendedKeyUsages": ["codeSigning"] },
+                    "requireRekorBundle": true
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+
+        assert!(matches!(
+            settings.signatures[0],
+            Signature::GithubActions(_)
+        ));
+        assert!(matches!(settings.signatures[1], Signature::Keyless(_)));
+        assert!(matches!(settings.signatures[2], Signature::KeylessMatch(_)));
+        assert!(matches!(settings.signatures[3], Signature::GitlabCi(_)));
+        assert!(matches!(
+            settings.signatures[4],
+            Signature::LeafCertificate(_)
+        ));
+    }
+
+    #[test]
+    fn deserialize_tagged_signatures() {
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "signatures": [
+                {
+                    "type": "keylessPrefix",
+                    "image": "ghcr.io/kubewarden/*",
+                    "keylessPrefix": [{ "issuer": "issuer", "url_prefix": "https://github.com" }]
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+
+        assert!(matches!(
+            settings.signatures[0],
+            Signature::KeylessPrefix(_)
+        ));
+    }
+
+    #[test]
+    fn deserialize_tagged_signature_reports_field_error_and_index() {
+        let result = serde_json::from_value::<Settings>(serde_json::json!({
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "githubActions": { "owner": "kubewarden" }
+                },
+                {
+                    "type": "pubKeys",
+                    "image": "ghcr.io/kubewarden/*",
+                    "pubkeys": ["key"]
+                }
+            ]
+        }));
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[1]: PubKeys: missing field `pubKeys`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn deserialize_signature_with_invalid_image_regex() {
+        let result = serde_json::from_value::<Settings>(serde_json::json!({
+            "signatures": [
+                {
+                    "type": "pubKeys",
+                    "imageRegex": "ghcr.io/(kubewarden",
+                    "pubKeys": ["key"]
+                }
+            ]
+        }));
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[0]: PubKeys: invalid imageRegex ghcr.io/(kubewarden"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn validate_settings_image_and_image_regex() {
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "imageRegex": "^ghcr\\.io/kubewarden/",
+                    "keyless": [{ "issuer": "issuer", "subject": "subject" }]
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+
+        let error = settings.validate().unwrap_err();
+        assert!(
+            error.contains("image and imageRegex cannot be set at the same time"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn validate_settings_minimum_key_strength() {
+        let settings_with = |settings: serde_json::Value| -> Settings {
+            serde_json::from_value(settings).expect("cannot deserialize settings")
+        };
+        let signatures = serde_json::json!([{
+            "image": "ghcr.io/acme/*",
+            "allOf": [{ "pubKeys": [P256_PUBLIC_KEY, RSA_1024_PUBLIC_KEY] }]
+        }]);
+
+        let settings = settings_with(serde_json::json!({ "signatures": signatures }));
+        assert_eq!(settings.minimum_key_strength, 112);
+        assert_eq!(
+            settings.validate().unwrap_err(),
+            "Pub key signature for image ghcr.io/acme/*: pubKeys[1]: RSA 1024 bits key provides 80 bits of security, less than the minimum of 112"
+        );
+
+        let settings = settings_with(serde_json::json!({
+            "signatures": signatures,
+            "minimumKeyStrength": 80
+        }));
+        assert!(settings.validate().is_ok());
+
+        let settings = settings_with(serde_json::json!({
+            "signatures": signatures,
+            "minimumKeyStrength": 192
+        }));
+        let error = settings.validate().unwrap_err();
+        assert!(
+            error.contains("pubKeys[0]: ECDSA P-256 key provides 128 bits of security"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn deserialize_normalizes_pem_bundles_and_der() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        use certificate_chain::tests::{
+            INTERMEDIATE_CERTIFICATE, LEAF_CERTIFICATE, ROOT_CERTIFICATE,
+        };
+
+        let der_key = STANDARD.encode(::pem::parse(P256_PUBLIC_KEY).unwrap().contents());
+        let bundle = format!("{INTERMEDIATE_CERTIFICATE}{ROOT_CERTIFICATE}");
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "trustRoots": {
+                "corporate-pki": { "certificates": [LEAF_CERTIFICATE], "certificateChain": [bundle] }
+            },
+            "signatures": [
+                {
+                    "image": "ghcr.io/acme/*",
+                    "anyOf": [{ "pubKeys": [der_key] }]
+                },
+                {
+                    "image": "registry.acme.lan/*",
+                    "trustRoots": ["corporate-pki"],
+                    "requireRekorBundle": true
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+
+        let Signature::Group(group) = &settings.signatures[0] else {
+            panic!("not a group");
+        };
+        let Signature::PubKeys(pub_keys) = &group.rules()[0] else {
+            panic!("not a pub keys rule");
+        };
+        assert_eq!(
+            public_key_algorithm(&pub_keys.pub_keys[0].pem),
+            Ok(public_key::KeyAlgorithm::EcdsaP256)
+        );
+
+        let Signature::Certificate(certificate) = &settings.signatures[1] else {
+            panic!("not a certificate rule");
+        };
+        let chain = certificate.certificate_chain.as_ref().unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(
+            certificate_chain::certificate_not_after(&chain[1].pem),
+            certificate_chain::certificate_not_after(ROOT_CERTIFICATE)
+        );
+        assert_eq!(
+            settings.trust_roots["corporate-pki"]
+                .certificate_chain
+                .as_ref()
+                .map(Vec::len),
+            Some(2)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn deserialize_and_validate_trust_roots() {
+        let settings = serde_json::json!({
+            "trustRoots": {
+                "maintainers": { "pubKeys": [P256_PUBLIC_KEY, P384_PUBLIC_KEY, RSA_3072_PUBLIC_KEY] },
+                "unused": { "pubKeys": ["not a key"] }
+            },
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "trustRoots": ["maintainers"],
+                    "threshold": 2
+                }
+            ]
+        });
+
+        let settings: Settings =
+            serde_json::from_value(settings).expect("cannot deserialize settings");
+        let Signature::PubKeys(pub_keys) = &settings.signatures[0] else {
+            panic!("not a pub keys rule");
+        };
+        assert_eq!(pub_keys.pub_keys.len(), 3);
+        assert_eq!(
+            settings.validate().unwrap_err(),
+            "Trust root unused: pubKeys[0]: invalid PEM: malformedframing"
+        );
+
+        // the trust root provides a key already listed by the rule
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "trustRoots": { "maintainers": { "pubKeys": [P256_PUBLIC_KEY] } },
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "pubKeys": [P256_PUBLIC_KEY],
+                    "trustRoots": ["maintainers"]
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+        let error = settings.validate().unwrap_err();
+        assert!(
+            error.contains("pubKeys[1] is the same signer as pubKeys[0]"),
+            "{error}"
+        );
+
+        // any of the signers of the trust root is enough
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "trustRoots": { "maintainers": { "pubKeys": [P256_PUBLIC_KEY, P384_PUBLIC_KEY] } },
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "trustRoots": ["maintainers"],
+                    "anyOfSigners": true
+                }
+            ]
+        }))
+        .expect("cannot deserialize settings");
+        let Signature::PubKeys(pub_keys) = &settings.signatures[0] else {
+            panic!("not a pub keys rule");
+        };
+        assert_eq!(pub_keys.required_signers(), Some(1));
+        assert!(settings.validate().is_ok());
+
+        let result = serde_json::from_value::<Settings>(serde_json::json!({
+            "signatures": [
+                { "image": "ghcr.io/kubewarden/*", "trustRoots": ["maintainers"] }
+            ]
+        }));
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[0]: unknown trust root maintainers"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn deserialize_signature_with_unknown_type() {
+        let result = serde_json::from_value::<Settings>(serde_json::json!({
+            "signatures": [
+                {
+                    "type": "notary",
+                    "image": "ghcr.io/kubewarden/*"
+                }
+            ]
+        }));
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[0]: invalid type: unknown variant `notary`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn deserialize_untagged_signature_reports_index() {
+        let result = serde_json::from_value::<Settings>(serde_json::json!({
+            "signatures": [
+                {
+                    "image": "ghcr.io/kubewarden/*",
+                    "pubkeys": ["key"]
+                }
+            ]
+        }));
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[0]: data did not match any signature type"),
+            "{error}"
+        );
+    }
 }
@@ -0,0 +1,175 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, LabelSelectorRequirement};
+use std::collections::BTreeMap;
+
+/// Returns true when the labels satisfy all the requirements of the selector.
+/// An empty selector matches everything.
+pub(crate) fn label_selector_matches(
+    selector: &LabelSelector,
+    labels: &BTreeMap<String, String>,
+) -> bool {
+    let match_labels = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .all(|(key, value)| labels.get(key) == Some(value));
+
+    match_labels
+        && selector
+            .match_expressions
+            .iter()
+            .flatten()
+            .all(|requirement| requirement_matches(requirement, labels))
+}
+
+fn requirement_matches(
+    requirement: &LabelSelectorRequirement,
+    labels: &BTreeMap<String, String>,
+) -> bool {
+    let value = labels.get(&requirement.key);
+    let values = requirement.values.as_deref().unwrap_or_default();
+
+    match requirement.operator.as_str() {
+        "In" => value.is_some_and(|v| values.contains(v)),
+        "NotIn" => value.is_none_or(|v| !values.contains(v)),
+        "Exists" => value.is_some(),
+        "DoesNotExist" => value.is_none(),
+        _ => false,
+    }
+}
+
+pub(crate) fn validate_label_selector(selector: &LabelSelector) -> Result<(), String> {
+    let errors: Vec<String> = selector
+        .match_expressions
+        .iter()
+        .flatten()
+        .filter_map(|requirement| {
+            let has_values = requirement
+                .values
+                .as_ref()
+                .is_some_and(|values| !values.is_empty());
+            match requirement.operator.as_str() {
+                "In" | "NotIn" if !has_values => Some(format!(
+                    "operator {} of key {} requires values",
+                    requirement.operator, requirement.key
+                )),
+                "Exists" | "DoesNotExist" if has_values => Some(format!(
+                    "operator {} of key {} does not accept values",
+                    requirement.operator, requirement.key
+                )),
+                "In" | "NotIn" | "Exists" | "DoesNotExist" => None,
+                operator => Some(format!(
+                    "unknown operator {operator} of key {}",
+                    requirement.key
+                )),
+            }
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("invalid namespace selector: {}", errors.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn requirement(
+        key: &str,
+        operator: &str,
+        values: Option<Vec<&str>>,
+    ) -> LabelSelectorRequirement {
+        LabelSelectorRequirement {
+            key: key.to_string(),
+            operator: operator.to_string(),
+            values: values.map(|v| v.into_iter().map(String::from).collect()),
+        }
+    }
+
+    #[rstest]
+    #[case::empty_selector(LabelSelector::default(), true)]
+    #[case::match_labels(
+        LabelSelector {
+            match_labels: Some(BTreeMap::from([(
+                "verify-images.acme.io/enforce".to_string(),
+                "true".to_string()
+            )])),
+            ..Default::default()
+        },
+        true
+    )]
+    #[case::match_labels_different_value(
+        LabelSelector {
+            match_labels: Some(BTreeMap::from([("env".to_string(), "staging".to_string())])),
+            ..Default::default()
+        },
+        false
+    )]
+    #[case::in_operator(
+        LabelSelector {
+            match_expressions: Some(vec![requirement("env", "In", Some(vec!["prod", "staging"]))]),
+            ..Default::default()
+        },
+        true
+    )]
+    #[case::not_in_operator(
+        LabelSelector {
+            match_expressions: Some(vec![requirement("env", "NotIn", Some(vec!["prod"]))]),
+            ..Default::default()
+        },
+        false
+    )]
+    #[case::exists_operator(
+        LabelSelector {
+            match_expressions: Some(vec![requirement("team", "Exists", None)]),
+            ..Default::default()
+        },
+        false
+    )]
+    #[case::does_not_exist_operator(
+        LabelSelector {
+            match_expressions: Some(vec![requirement("team", "DoesNotExist", None)]),
+            ..Default::default()
+        },
+        true
+    )]
+    fn selector_matching(#[case] selector: LabelSelector, #[case] expected: bool) {
+        let labels = BTreeMap::from([
+            (
+                "verify-images.acme.io/enforce".to_string(),
+                "true".to_string(),
+            ),
+            ("env".to_string(), "prod".to_string()),
+        ]);
+
+        assert_eq!(label_selector_matches(&selector, &labels), expected);
+    }
+
+    #[test]
+    fn selector_validation() {
+        let selector = LabelSelector {
+            match_expressions: Some(vec![
+                requirement("env", "In", Some(vec!["prod"])),
+                requirement("team", "Exists", None),
+            ]),
+            ..Default::default()
+        };
+        assert!(validate_label_selector(&selector).is_ok());
+
+        let selector = LabelSelector {
+            match_expressions: Some(vec![
+                requirement("env", "In", None),
+                requirement("team", "Exists", Some(vec!["a"])),
+                requirement("tier", "Equals", Some(vec!["a"])),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_label_selector(&selector).unwrap_err(),
+            "invalid namespace selector: operator In of key env requires values, operator Exists of key team does not accept values, unknown operator Equals of key tier"
+        );
+    }
+}
@@ -0,0 +1,533 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kubewarden::host_capabilities::kubernetes::GetResourceRequest;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use x509_parser::prelude::{FromDer, SubjectPublicKeyInfo, X509Certificate};
+
+use super::validation_helpers::validate_vector_of_pem_strings;
+use super::validity::Validity;
+
+#[cfg(test)]
+use crate::tests::mock_kubernetes_sdk::get_resource;
+#[cfg(not(test))]
+use kubewarden::host_capabilities::kubernetes::get_resource;
+
+/// Key of a ConfigMap or of a Secret
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct KeyReference {
+    pub(crate) namespace: String,
+    pub(crate) name: String,
+    pub(crate) key: String,
+}
+
+/// PEM data stored in the cluster, read when the requests are validated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum PemReference {
+    ConfigMapRef(KeyReference),
+    SecretRef(KeyReference),
+}
+
+impl fmt::Display for PemReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, reference) = match self {
+            PemReference::ConfigMapRef(reference) => ("ConfigMap", reference),
+            PemReference::SecretRef(reference) => ("Secret", reference),
+        };
+        write!(
+            f,
+            "key {} of {kind} {}/{}",
+            reference.key, reference.namespace, reference.name
+        )
+    }
+}
+
+impl PemReference {
+    fn key_reference(&self) -> &KeyReference {
+        match self {
+            PemReference::ConfigMapRef(reference) | PemReference::SecretRef(reference) => reference,
+        }
+    }
+
+    fn request(&self, kind: &str) -> GetResourceRequest {
+        let reference = self.key_reference();
+        GetResourceRequest {
+            api_version: "v1".to_string(),
+            kind: kind.to_string(),
+            name: reference.name.clone(),
+            namespace: Some(reference.namespace.clone()),
+            disable_cache: false,
+        }
+    }
+
+    /// Reads the referenced data from the cluster
+    fn fetch(&self) -> Result<String, String> {
+        let key = &self.key_reference().key;
+        let data = match self {
+            PemReference::ConfigMapRef(_) => get_resource::<ConfigMap>(&self.request("ConfigMap"))
+                .map_err(|e| e.to_string())?
+                .data
+                .and_then(|mut data| data.remove(key)),
+            PemReference::SecretRef(_) => get_resource::<Secret>(&self.request("Secret"))
+                .map_err(|e| e.to_string())?
+                .data
+                .and_then(|mut data| data.remove(key))
+                .map(|data| String::from_utf8(data.0).map_err(|_| "data is not UTF-8".to_string()))
+                .transpose()?,
+        };
+        data.ok_or_else(|| "key not found".to_string())
+    }
+
+    fn is_complete(&self) -> bool {
+        let reference = self.key_reference();
+        [&reference.namespace, &reference.name, &reference.key]
+            .iter()
+            .all(|field| !field.is_empty())
+    }
+}
+
+/// PEM encoded key or certificate, optionally used only during a validity
+/// window. It is provided either as a plain PEM string or as an object with
+/// the `pem` field, or a `configMapRef` or `secretRef` field, and the
+/// `notBefore` and `notAfter` fields.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(from = "PemEntry", into = "PemEntry")]
+pub(crate) struct PemMaterial {
+    /// PEM data, empty until the reference is resolved
+    pub(crate) pem: String,
+    /// ConfigMap or Secret holding the PEM data
+    pub(crate) reference: Option<PemReference>,
+    pub(crate) validity: Validity,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PemEntry {
+    Pem(String),
+    Inline {
+        pem: String,
+        #[serde(flatten)]
+        validity: Validity,
+    },
+    Reference {
+        #[serde(flatten)]
+        reference: PemReference,
+        #[serde(flatten)]
+        validity: Validity,
+    },
+}
+
+impl From<PemEntry> for PemMaterial {
+    fn from(entry: PemEntry) -> Self {
+        match entry {
+            PemEntry::Pem(pem) => PemMaterial {
+                pem,
+                ..Default::default()
+            },
+            PemEntry::Inline { pem, validity } => PemMaterial {
+                pem,
+                reference: None,
+                validity,
+            },
+            PemEntry::Reference {
+                reference,
+                validity,
+            } => PemMaterial {
+                pem: String::new(),
+                reference: Some(reference),
+                validity,
+            },
+        }
+    }
+}
+
+impl From<PemMaterial> for PemEntry {
+    fn from(material: PemMaterial) -> Self {
+        match material.reference {
+            Some(reference) => PemEntry::Reference {
+                reference,
+                validity: material.validity,
+            },
+            None if material.validity.is_set() => PemEntry::Inline {
+                pem: material.pem,
+                validity: material.validity,
+            },
+            None => PemEntry::Pem(material.pem),
+        }
+    }
+}
+
+impl From<&str> for PemMaterial {
+    fn from(pem: &str) -> Self {
+        PemEntry::Pem(pem.to_string()).into()
+    }
+}
+
+impl PemMaterial {
+    /// Reads the referenced PEM data, validating it like the inline one
+    pub(crate) fn resolve(&mut self) -> Result<(), String> {
+        let Some(reference) = &self.reference else {
+            return Ok(());
+        };
+        let pem = reference
+            .fetch()
+            .map_err(|e| format!("cannot read the {reference}: {e}"))?;
+        let pems = normalize_pem(&pem);
+        let pems: Vec<&str> = pems.iter().map(String::as_str).collect();
+        validate_vector_of_pem_strings(&pems)
+            .map_err(|_| format!("the {reference} does not hold PEM data"))?;
+        self.pem = pem;
+        Ok(())
+    }
+}
+
+/// Splits a bundle of concatenated PEM objects, and encodes as PEM a base64
+/// encoded DER certificate or public key. Any other data is returned as is,
+/// to be reported by the validation.
+pub(crate) fn normalize_pem(data: &str) -> Vec<String> {
+    if let Ok(pems) = ::pem::parse_many(data) {
+        match pems.len() {
+            0 => {}
+            1 => return vec![data.to_string()],
+            _ => return pems.iter().map(encode_pem).collect(),
+        }
+    }
+
+    let Ok(der) = STANDARD.decode(data.split_whitespace().collect::<String>()) else {
+        return vec![data.to_string()];
+    };
+    let tag = if X509Certificate::from_der(&der).is_ok() {
+        "CERTIFICATE"
+    } else if SubjectPublicKeyInfo::from_der(&der).is_ok() {
+        "PUBLIC KEY"
+    } else {
+        return vec![data.to_string()];
+    };
+    vec![encode_pem(&::pem::Pem::new(tag, der))]
+}
+
+fn encode_pem(pem: &::pem::Pem) -> String {
+    ::pem::encode_config(
+        pem,
+        ::pem::EncodeConfig::new().set_line_ending(::pem::LineEnding::LF),
+    )
+}
+
+/// Replaces the materials holding PEM bundles or base64 encoded DER with one
+/// material per PEM object, keeping their reference and validity window
+pub(crate) fn normalize_pem_materials(materials: &mut Vec<PemMaterial>) {
+    *materials = materials
+        .drain(..)
+        .flat_map(|material| {
+            normalize_pem(&material.pem)
+                .into_iter()
+                .map(move |pem| PemMaterial {
+                    pem,
+                    ..material.clone()
+                })
+        })
+        .collect();
+}
+
+/// Identity of the signer holding the PEM data: its DER content, so that the
+/// same key or certificate is recognized whatever its encoding
+pub(crate) fn signer_identity(pem: &str) -> Vec<u8> {
+    match ::pem::parse(pem.trim()) {
+        Ok(pem) => pem.into_contents(),
+        Err(_) => pem.as_bytes().to_vec(),
+    }
+}
+
+/// Signer identities of the materials holding PEM data, with their index.
+/// The references are skipped until they are resolved.
+pub(crate) fn signer_identities(materials: &[PemMaterial]) -> Vec<(usize, Vec<u8>)> {
+    materials
+        .iter()
+        .enumerate()
+        .filter(|(_, material)| !material.pem.is_empty())
+        .map(|(index, material)| (index, signer_identity(&material.pem)))
+        .collect()
+}
+
+/// Validates the PEM data provided inline and the references. The referenced
+/// data is validated once resolved
+pub(crate) fn validate_pem_materials(
+    materials: &[PemMaterial],
+) -> Result<(), validator::ValidationError> {
+    let inline: Vec<&str> = materials
+        .iter()
+        .filter(|material| material.reference.is_none())
+        .map(|material| material.pem.as_str())
+        .collect();
+    validate_vector_of_pem_strings(&inline)?;
+    validate_references(materials)
+}
+
+/// Validates that the references name the key of a resource
+pub(crate) fn validate_references(
+    materials: &[PemMaterial],
+) -> Result<(), validator::ValidationError> {
+    if materials
+        .iter()
+        .filter_map(|material| material.reference.as_ref())
+        .all(PemReference::is_complete)
+    {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new(
+            "references require a namespace, a name and a key",
+        ))
+    }
+}
+
+/// Returns the PEM data usable at the given time, along with its index
+pub(crate) fn active_pems(materials: &[PemMaterial], now: DateTime<Utc>) -> Vec<(usize, String)> {
+    materials
+        .iter()
+        .enumerate()
+        .filter(|(_, material)| material.validity.is_active(now))
+        .map(|(index, material)| (index, material.pem.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::certificate_chain::tests::{INTERMEDIATE_CERTIFICATE, ROOT_CERTIFICATE};
+    use super::super::public_key::tests::P256_PUBLIC_KEY;
+    use super::super::validation_helpers::tests::PEM_DATA;
+    use super::*;
+    use crate::tests::mock_kubernetes_sdk;
+    use chrono::TimeZone;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use k8s_openapi::ByteString;
+    use serial_test::serial;
+    use std::collections::BTreeMap;
+
+    fn date(year: i32, month: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn deserialize_pem_materials() {
+        let materials: Vec<PemMaterial> = serde_json::from_value(serde_json::json!([
+            "key1",
+            { "pem": "key2", "notAfter": "2025-07-01T00:00:00Z" },
+            {
+                "secretRef": { "namespace": "security", "name": "signing-keys", "key": "key3" },
+                "notBefore": "2025-06-01T00:00:00Z"
+            }
+        ]))
+        .expect("cannot deserialize PEM materials");
+
+        assert_eq!(materials[0], PemMaterial::from("key1"));
+        assert_eq!(materials[1].pem, "key2");
+        assert_eq!(materials[1].validity.not_after, Some(date(2025, 7)));
+        assert_eq!(
+            materials[2].reference,
+            Some(PemReference::SecretRef(KeyReference {
+                namespace: "security".to_string(),
+                name: "signing-keys".to_string(),
+                key: "key3".to_string(),
+            }))
+        );
+        assert_eq!(materials[2].validity.not_before, Some(date(2025, 6)));
+
+        assert_eq!(
+            active_pems(&materials, date(2025, 8)),
+            vec![(0, "key1".to_string()), (2, "".to_string())]
+        );
+        assert_eq!(
+            serde_json::to_value(&materials).unwrap(),
+            serde_json::json!([
+                "key1",
+                { "pem": "key2", "notAfter": "2025-07-01T00:00:00Z" },
+                {
+                    "secretRef": { "namespace": "security", "name": "signing-keys", "key": "key3" },
+                    "notBefore": "2025-06-01T00:00:00Z"
+                }
+            ])
+        );
+    }
+
+    fn base64_der(pem: &str) -> String {
+        STANDARD.encode(::pem::parse(pem).unwrap().contents())
+    }
+
+    fn assert_same_pem(actual: &str, expected: &str) {
+        assert_eq!(
+            ::pem::parse(actual).unwrap(),
+            ::pem::parse(expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn normalize_bundles_and_der() {
+        assert_eq!(normalize_pem(ROOT_CERTIFICATE), vec![ROOT_CERTIFICATE]);
+
+        let pems = normalize_pem(&format!("{INTERMEDIATE_CERTIFICATE}{ROOT_CERTIFICATE}"));
+        assert_eq!(pems.len(), 2);
+        assert_same_pem(&pems[0], INTERMEDIATE_CERTIFICATE);
+        assert_same_pem(&pems[1], ROOT_CERTIFICATE);
+
+        let pems = normalize_pem(&base64_der(ROOT_CERTIFICATE));
+        assert_eq!(pems.len(), 1);
+        assert_same_pem(&pems[0], ROOT_CERTIFICATE);
+
+        let pems = normalize_pem(&base64_der(P256_PUBLIC_KEY));
+        assert_eq!(pems.len(), 1);
+        assert_same_pem(&pems[0], P256_PUBLIC_KEY);
+
+        for data in ["not PEM", "aGVsbG8=", ""] {
+            assert_eq!(normalize_pem(data), vec![data]);
+        }
+    }
+
+    #[test]
+    fn normalize_materials() {
+        let validity = Validity {
+            not_after: Some(date(2030, 1)),
+            ..Default::default()
+        };
+        let mut materials = vec![
+            PemMaterial {
+                pem: format!("{INTERMEDIATE_CERTIFICATE}{ROOT_CERTIFICATE}"),
+                reference: None,
+                validity: validity.clone(),
+            },
+            base64_der(PEM_DATA).as_str().into(),
+        ];
+        normalize_pem_materials(&mut materials);
+
+        assert_eq!(materials.len(), 3);
+        assert_same_pem(&materials[0].pem, INTERMEDIATE_CERTIFICATE);
+        assert_same_pem(&materials[1].pem, ROOT_CERTIFICATE);
+        assert_same_pem(&materials[2].pem, PEM_DATA);
+        assert_eq!(materials[0].validity, validity);
+        assert_eq!(materials[1].validity, validity);
+        assert!(!materials[2].validity.is_set());
+    }
+
+    #[test]
+    fn validation_skips_references() {
+        let reference = |name: &str| PemMaterial {
+            reference: Some(PemReference::ConfigMapRef(KeyReference {
+                namespace: "security".to_string(),
+                name: name.to_string(),
+                key: "cosign.pub".to_string(),
+            })),
+            ..Default::default()
+        };
+
+        assert!(validate_pem_materials(&[PEM_DATA.into(), reference("keys")]).is_ok());
+        assert!(validate_pem_materials(&["not PEM".into(), reference("keys")]).is_err());
+        assert_eq!(
+            validate_pem_materials(&[reference("")]).unwrap_err().code,
+            "references require a namespace, a name and a key"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn resolve_secret_reference() {
+        let ctx = mock_kubernetes_sdk::get_resource_context();
+        ctx.expect::<Secret>()
+            .times(1)
+            .withf(|req| {
+                req.kind == "Secret"
+                    && req.name == "signing-keys"
+                    && req.namespace.as_deref() == Some("security")
+            })
+            .returning(|_| {
+                Ok(Secret {
+                    metadata: ObjectMeta::default(),
+                    data: Some(BTreeMap::from([(
+                        "cosign.pub".to_string(),
+                        ByteString(PEM_DATA.as_bytes().to_vec()),
+                    )])),
+                    ..Default::default()
+                })
+            });
+
+        let mut material = PemMaterial {
+            reference: Some(PemReference::SecretRef(KeyReference {
+                namespace: "security".to_string(),
+                name: "signing-keys".to_string(),
+                key: "cosign.pub".to_string(),
+            })),
+            ..Default::default()
+        };
+        material.resolve().expect("cannot resolve the reference");
+        assert_eq!(material.pem, PEM_DATA);
+    }
+
+    #[test]
+    #[serial]
+    fn resolve_config_map_reference_with_der() {
+        let ctx = mock_kubernetes_sdk::get_resource_context();
+        ctx.expect::<ConfigMap>().times(1).returning(|_| {
+            Ok(ConfigMap {
+                data: Some(BTreeMap::from([(
+                    "cosign.pub".to_string(),
+                    base64_der(P256_PUBLIC_KEY),
+                )])),
+                ..Default::default()
+            })
+        });
+
+        let mut materials = vec![PemMaterial {
+            reference: Some(PemReference::ConfigMapRef(KeyReference {
+                namespace: "security".to_string(),
+                name: "keys".to_string(),
+                key: "cosign.pub".to_string(),
+            })),
+            ..Default::default()
+        }];
+        materials[0]
+            .resolve()
+            .expect("cannot resolve the reference");
+        normalize_pem_materials(&mut materials);
+        assert_same_pem(&materials[0].pem, P256_PUBLIC_KEY);
+        assert!(materials[0].reference.is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn resolve_config_map_reference_errors() {
+        let ctx = mock_kubernetes_sdk::get_resource_context();
+        ctx.expect::<ConfigMap>().times(2).returning(|req| {
+            Ok(ConfigMap {
+                data: Some(BTreeMap::from([(
+                    "cosign.pub".to_string(),
+                    format!("not PEM, from {}", req.name),
+                )])),
+                ..Default::default()
+            })
+        });
+
+        let mut material = PemMaterial {
+            reference: Some(PemReference::ConfigMapRef(KeyReference {
+                namespace: "security".to_string(),
+                name: "keys".to_string(),
+                key: "cosign.pub".to_string(),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            material.resolve().unwrap_err(),
+            "the key cosign.pub of ConfigMap security/keys does not hold PEM data"
+        );
+
+        material.reference = Some(PemReference::ConfigMapRef(KeyReference {
+            namespace: "security".to_string(),
+            name: "keys".to_string(),
+            key: "other.pub".to_string(),
+        }));
+        assert_eq!(
+            material.resolve().unwrap_err(),
+            "cannot read the key other.pub of ConfigMap security/keys: key not found"
+        );
+    }
+}
@@ -1,38 +1,88 @@
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::fmt;
 use validator::Validate;
 
-use super::validation_helpers::validate_vector_of_pem_strings;
+use super::image_regex::{image_pattern, validate_image_pattern, ImageRegex};
+use super::pem_material::{signer_identities, PemMaterial};
+use super::public_key::validate_public_keys;
+use super::rule_scope::RuleScope;
+use super::threshold::{
+    required_signers, threshold_validation_error, validate_distinct_signers, validate_threshold,
+};
 
-#[derive(Serialize, Deserialize, Validate, Debug)]
+#[derive(Serialize, Deserialize, Validate, Debug, Default)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_pub_keys_threshold"))]
+#[validate(schema(function = "validate_pub_keys_image"))]
 pub(crate) struct PubKeys {
-    #[validate(length(min = 1))]
+    #[serde(default)]
     pub(crate) image: String,
-    #[validate(length(min = 1), custom(function = "validate_vector_of_pem_strings"))]
-    pub(crate) pub_keys: Vec<String>,
-    pub(crate) annotations: Option<BTreeMap<String, String>>,
+    /// Optional - Regular expression matched against the images, alternative to `image`
+    pub(crate) image_regex: Option<ImageRegex>,
+    /// Where and when the rule is enforced, and the annotations its signatures
+    /// must have
+    #[serde(flatten)]
+    pub(crate) scope: RuleScope,
+    /// PEM encoded ECDSA P-256 or P-384, RSA or Ed25519 keys, optionally with
+    /// the period of time during which they are used. Outside of it the key
+    /// is ignored
+    #[validate(length(min = 1), custom(function = "validate_public_keys"))]
+    pub(crate) pub_keys: Vec<PemMaterial>,
+    /// Optional - Minimum number of keys that must have signed the image.
+    /// When not specified, all the keys are required
+    pub(crate) threshold: Option<usize>,
+    /// Optional - Accept the image when it has been signed by any of the keys
+    #[serde(default)]
+    pub(crate) any_of_signers: bool,
+}
+
+impl PubKeys {
+    /// Number of keys that must have signed the image, `None` when all of them are required
+    pub(crate) fn required_signers(&self) -> Option<usize> {
+        required_signers(self.threshold, self.any_of_signers)
+    }
+}
+
+fn validate_pub_keys_threshold(pub_keys: &PubKeys) -> Result<(), validator::ValidationError> {
+    validate_distinct_signers("pubKeys", signer_identities(&pub_keys.pub_keys))
+        .and_then(|_| {
+            validate_threshold(
+                pub_keys.threshold,
+                pub_keys.any_of_signers,
+                pub_keys.pub_keys.len(),
+            )
+        })
+        .map_err(threshold_validation_error)
+}
+
+fn validate_pub_keys_image(pubkeys: &PubKeys) -> Result<(), validator::ValidationError> {
+    validate_image_pattern(&pubkeys.image, &pubkeys.image_regex)
 }
 
 impl fmt::Display for PubKeys {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Pub key signature for image {}", self.image)
+        write!(
+            f,
+            "Pub key signature for image {}",
+            image_pattern(&self.image, &self.image_regex)
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::public_key::tests::{P256_PUBLIC_KEY, RSA_3072_PUBLIC_KEY};
     use super::super::validation_helpers::tests::PEM_DATA;
     use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
     use validator::Validate;
 
     #[test]
     fn validation_pass() {
         let pub_keys = PubKeys {
             image: "foo".to_string(),
-            pub_keys: vec![PEM_DATA.to_string()],
-            annotations: None,
+            pub_keys: vec![P256_PUBLIC_KEY.into()],
+            ..Default::default()
         };
         assert!(pub_keys.validate().is_ok());
     }
@@ -41,8 +91,8 @@ mod tests {
     fn validation_fails_because_missing_values() {
         let pub_keys = PubKeys {
             image: "".to_string(),
-            pub_keys: vec![PEM_DATA.to_string()],
-            annotations: None,
+            pub_keys: vec![P256_PUBLIC_KEY.into()],
+            ..Default::default()
         };
 
         assert!(pub_keys.validate().is_err());
@@ -52,18 +102,97 @@ mod tests {
     fn validation_fails_because_pub_key_is_not_pem_encoded() {
         let pub_keys = PubKeys {
             image: "foo".to_string(),
-            pub_keys: vec!["hello".to_string()],
-            annotations: None,
+            pub_keys: vec!["hello".into()],
+            ..Default::default()
+        };
+
+        assert!(pub_keys.validate().is_err());
+
+        let pub_keys = PubKeys {
+            image: "foo".to_string(),
+            pub_keys: vec![P256_PUBLIC_KEY.into(), "hello".into()],
+            ..Default::default()
+        };
+
+        assert!(pub_keys.validate().is_err());
+    }
+
+    #[test]
+    fn validation_fails_because_pub_key_is_a_certificate() {
+        let pub_keys = PubKeys {
+            image: "foo".to_string(),
+            pub_keys: vec![P256_PUBLIC_KEY.into(), PEM_DATA.into()],
+            ..Default::default()
+        };
+
+        let error = pub_keys.validate().unwrap_err().to_string();
+        assert!(
+            error.contains("pubKeys[1]: a certificate has been provided instead of a public key"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn validation_fails_because_threshold_is_too_high() {
+        let pub_keys = PubKeys {
+            image: "foo".to_string(),
+            pub_keys: vec![P256_PUBLIC_KEY.into(), RSA_3072_PUBLIC_KEY.into()],
+            threshold: Some(3),
+            ..Default::default()
         };
 
         assert!(pub_keys.validate().is_err());
 
+        let pub_keys = PubKeys {
+            threshold: Some(2),
+            ..pub_keys
+        };
+
+        assert!(pub_keys.validate().is_ok());
+    }
+
+    #[test]
+    fn deserialize_any_of_signers() {
+        let pub_keys: PubKeys = serde_json::from_value(serde_json::json!({
+            "image": "foo",
+            "pubKeys": [P256_PUBLIC_KEY, RSA_3072_PUBLIC_KEY],
+            "anyOfSigners": true
+        }))
+        .unwrap();
+
+        assert!(pub_keys.validate().is_ok());
+        assert_eq!(pub_keys.required_signers(), Some(1));
+    }
+
+    #[test]
+    fn validation_fails_because_pub_key_is_duplicated() {
         let pub_keys = PubKeys {
             image: "foo".to_string(),
-            pub_keys: vec![PEM_DATA.to_string(), "hello".to_string()],
-            annotations: None,
+            pub_keys: vec![P256_PUBLIC_KEY.into(), P256_PUBLIC_KEY.into()],
+            threshold: Some(2),
+            ..Default::default()
         };
 
+        let error = pub_keys.validate().unwrap_err().to_string();
+        assert!(
+            error.contains("pubKeys[1] is the same signer as pubKeys[0]"),
+            "{error}"
+        );
+
+        // the same key, base64 DER encoded
+        let mut settings: crate::settings::Settings = serde_json::from_value(serde_json::json!({
+            "signatures": [{
+                "image": "foo",
+                "pubKeys": [
+                    P256_PUBLIC_KEY,
+                    STANDARD.encode(::pem::parse(P256_PUBLIC_KEY).unwrap().contents())
+                ]
+            }]
+        }))
+        .expect("cannot deserialize settings");
+        let crate::settings::Signature::PubKeys(pub_keys) = settings.signatures.remove(0) else {
+            panic!("not a pub keys rule");
+        };
         assert!(pub_keys.validate().is_err());
     }
 }
@@ -0,0 +1,209 @@
+use std::fmt;
+use x509_parser::oid_registry::{
+    OID_EC_P256, OID_KEY_TYPE_EC_PUBLIC_KEY, OID_NIST_EC_P384, OID_PKCS1_RSAENCRYPTION,
+    OID_SIG_ED25519,
+};
+use x509_parser::prelude::*;
+use x509_parser::public_key::PublicKey;
+
+use super::pem_material::{validate_references, PemMaterial};
+
+/// Algorithm of a public key supported by cosign
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum KeyAlgorithm {
+    EcdsaP256,
+    EcdsaP384,
+    /// RSA key, with the size of its modulus in bits
+    Rsa(usize),
+    Ed25519,
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyAlgorithm::EcdsaP256 => write!(f, "ECDSA P-256"),
+            KeyAlgorithm::EcdsaP384 => write!(f, "ECDSA P-384"),
+            KeyAlgorithm::Rsa(bits) => write!(f, "RSA {bits} bits"),
+            KeyAlgorithm::Ed25519 => write!(f, "Ed25519"),
+        }
+    }
+}
+
+impl KeyAlgorithm {
+    /// Bits of security provided by the key, as estimated by NIST SP 800-57
+    pub(crate) fn strength(&self) -> u32 {
+        match self {
+            KeyAlgorithm::EcdsaP256 | KeyAlgorithm::Ed25519 => 128,
+            KeyAlgorithm::EcdsaP384 => 192,
+            KeyAlgorithm::Rsa(bits) => match bits {
+                15360.. => 256,
+                7680.. => 192,
+                3072.. => 128,
+                2048.. => 112,
+                1024.. => 80,
+                _ => 0,
+            },
+        }
+    }
+
+    pub(crate) fn validate_strength(&self, minimum_strength: u32) -> Result<(), String> {
+        if self.strength() < minimum_strength {
+            return Err(format!(
+                "{self} key provides {} bits of security, less than the minimum of {minimum_strength}",
+                self.strength()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parses the PEM encoded SubjectPublicKeyInfo and returns the algorithm of
+/// the key
+pub(crate) fn public_key_algorithm(pem: &str) -> Result<KeyAlgorithm, String> {
+    let pem = ::pem::parse(pem.trim()).map_err(|e| format!("invalid PEM: {e}"))?;
+    match pem.tag() {
+        "PUBLIC KEY" => {}
+        tag if tag.ends_with("PRIVATE KEY") => {
+            return Err(
+                "a private key has been provided instead of a public key, it must be replaced and kept secret"
+                    .to_string(),
+            )
+        }
+        "CERTIFICATE" => {
+            return Err(
+                "a certificate has been provided instead of a public key, use a certificate rule instead"
+                    .to_string(),
+            )
+        }
+        tag => return Err(format!("{tag} found instead of a PUBLIC KEY")),
+    }
+
+    let (_, spki) = SubjectPublicKeyInfo::from_der(pem.contents())
+        .map_err(|e| format!("invalid public key: {e}"))?;
+    let algorithm = &spki.algorithm.algorithm;
+    if *algorithm == OID_SIG_ED25519 {
+        return Ok(KeyAlgorithm::Ed25519);
+    }
+    if *algorithm == OID_PKCS1_RSAENCRYPTION {
+        return match spki.parsed() {
+            Ok(PublicKey::RSA(key)) => Ok(KeyAlgorithm::Rsa(key.key_size())),
+            _ => Err("invalid RSA public key".to_string()),
+        };
+    }
+    if *algorithm == OID_KEY_TYPE_EC_PUBLIC_KEY {
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|parameters| parameters.as_oid().ok());
+        return match curve {
+            Some(curve) if curve == OID_EC_P256 => Ok(KeyAlgorithm::EcdsaP256),
+            Some(curve) if curve == OID_NIST_EC_P384 => Ok(KeyAlgorithm::EcdsaP384),
+            Some(curve) => Err(format!("unsupported elliptic curve {curve}")),
+            None => Err("elliptic curve not specified".to_string()),
+        };
+    }
+    Err(format!("unsupported key algorithm {algorithm}"))
+}
+
+/// Validates the public keys provided inline and the references. The
+/// referenced keys are validated once resolved
+pub(crate) fn validate_public_keys(
+    materials: &[PemMaterial],
+) -> Result<(), validator::ValidationError> {
+    let errors: Vec<String> = materials
+        .iter()
+        .enumerate()
+        .filter(|(_, material)| material.reference.is_none())
+        .filter_map(|(index, material)| {
+            public_key_algorithm(&material.pem)
+                .err()
+                .map(|e| format!("pubKeys[{index}]: {e}"))
+        })
+        .collect();
+
+    if errors.is_empty() {
+        validate_references(materials)
+    } else {
+        Err(validator::ValidationError::new("pubKeys").with_message(errors.join(", ").into()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::validation_helpers::tests::PEM_DATA;
+    use super::*;
+    use rstest::rstest;
+
+    pub(crate) const P256_PUBLIC_KEY: &str = include_str!("../../test_data/public_keys/p256.pub");
+    pub(crate) const RSA_1024_PUBLIC_KEY: &str =
+        include_str!("../../test_data/public_keys/rsa1024.pub");
+    pub(crate) const P384_PUBLIC_KEY: &str = include_str!("../../test_data/public_keys/p384.pub");
+    pub(crate) const RSA_3072_PUBLIC_KEY: &str =
+        include_str!("../../test_data/public_keys/rsa3072.pub");
+    const P256_PRIVATE_KEY: &str = include_str!("../../test_data/public_keys/p256.key");
+
+    #[rstest]
+    #[case::p256(P256_PUBLIC_KEY, KeyAlgorithm::EcdsaP256, 128)]
+    #[case::p384(P384_PUBLIC_KEY, KeyAlgorithm::EcdsaP384, 192)]
+    #[case::ed25519(
+        include_str!("../../test_data/public_keys/ed25519.pub"),
+        KeyAlgorithm::Ed25519,
+        128
+    )]
+    #[case::rsa_1024(RSA_1024_PUBLIC_KEY, KeyAlgorithm::Rsa(1024), 80)]
+    #[case::rsa_3072(RSA_3072_PUBLIC_KEY, KeyAlgorithm::Rsa(3072), 128)]
+    fn supported_algorithms(
+        #[case] pem: &str,
+        #[case] expected: KeyAlgorithm,
+        #[case] strength: u32,
+    ) {
+        let algorithm = public_key_algorithm(pem).expect("cannot parse the public key");
+        assert_eq!(algorithm, expected);
+        assert_eq!(algorithm.strength(), strength);
+    }
+
+    #[rstest]
+    #[case::private_key(
+        P256_PRIVATE_KEY,
+        "a private key has been provided instead of a public key, it must be replaced and kept secret"
+    )]
+    #[case::certificate(
+        PEM_DATA,
+        "a certificate has been provided instead of a public key, use a certificate rule instead"
+    )]
+    #[case::unsupported_curve(
+        include_str!("../../test_data/public_keys/secp256k1.pub"),
+        "unsupported elliptic curve 1.3.132.0.10"
+    )]
+    #[case::not_a_key(
+        "-----BEGIN PUBLIC KEY-----\naGVsbG8=\n-----END PUBLIC KEY-----",
+        "invalid public key"
+    )]
+    fn rejected_keys(#[case] pem: &str, #[case] error: &str) {
+        let e = public_key_algorithm(pem).unwrap_err();
+        assert!(e.starts_with(error), "{e}");
+    }
+
+    #[test]
+    fn strength_validation() {
+        assert!(KeyAlgorithm::Rsa(2048).validate_strength(112).is_ok());
+        assert_eq!(
+            KeyAlgorithm::Rsa(2048).validate_strength(128).unwrap_err(),
+            "RSA 2048 bits key provides 112 bits of security, less than the minimum of 128"
+        );
+    }
+
+    #[test]
+    fn public_keys_validation() {
+        assert!(validate_public_keys(&[P256_PUBLIC_KEY.into()]).is_ok());
+
+        let error = validate_public_keys(&[P256_PUBLIC_KEY.into(), P256_PRIVATE_KEY.into()])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("pubKeys[1]: a private key has been provided"),
+            "{error}"
+        );
+    }
+}
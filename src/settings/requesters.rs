@@ -0,0 +1,163 @@
+use kubewarden::request::UserInfo;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wildmatch::WildMatch;
+
+use super::{deserialize_optional_signatures, Signature};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Requester {
+    /// Optional - Usernames of the requesters, supports wildcards
+    /// (e.g.: `system:serviceaccount:kube-system:*`)
+    pub(crate) usernames: Option<Vec<String>>,
+    /// Optional - Groups of the requesters, supports wildcards
+    pub(crate) groups: Option<Vec<String>>,
+    /// Justification of the exemption or of the different rules
+    pub(crate) reason: String,
+    /// Optional - Signatures verified for the requests made by the requesters,
+    /// instead of the top level ones. When not specified, the requests are
+    /// exempted from all the signature checks
+    #[serde(default, deserialize_with = "deserialize_optional_signatures")]
+    pub(crate) signatures: Option<Vec<Signature>>,
+}
+
+impl fmt::Display for Requester {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Requester {}", self.reason)
+    }
+}
+
+impl Requester {
+    /// Returns true when the username or one of the groups of the user match
+    pub(crate) fn matches(&self, user_info: &UserInfo) -> bool {
+        let matches_any = |patterns: &Option<Vec<String>>, value: &str| {
+            patterns
+                .iter()
+                .flatten()
+                .any(|pattern| WildMatch::new(pattern).matches(value))
+        };
+
+        matches_any(&self.usernames, &user_info.username)
+            || user_info
+                .groups
+                .iter()
+                .any(|group| matches_any(&self.groups, group))
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.reason.is_empty() {
+            return Err("no reason provided".to_string());
+        }
+
+        let has_patterns =
+            |patterns: &Option<Vec<String>>| patterns.as_ref().is_some_and(|p| !p.is_empty());
+        if !has_patterns(&self.usernames) && !has_patterns(&self.groups) {
+            return Err("no usernames or groups provided".to_string());
+        }
+
+        let Some(signatures) = &self.signatures else {
+            return Ok(());
+        };
+        if signatures.is_empty() {
+            return Err("signatures must not be empty".to_string());
+        }
+
+        let validation_errors: Vec<String> = signatures
+            .iter()
+            .filter_map(|s| s.validate().err().map(|e| format!("{s}: {e}")))
+            .collect();
+
+        if validation_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validation_errors.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn user_info(username: &str, groups: &[&str]) -> UserInfo {
+        UserInfo {
+            username: username.to_string(),
+            groups: groups.iter().map(|g| g.to_string()).collect::<HashSet<_>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requester_matching() {
+        let requester = Requester {
+            usernames: Some(vec!["system:serviceaccount:kube-system:*".to_string()]),
+            groups: Some(vec!["system:nodes".to_string()]),
+            reason: "cluster components".to_string(),
+            ..Default::default()
+        };
+
+        assert!(requester.matches(&user_info(
+            "system:serviceaccount:kube-system:cluster-autoscaler",
+            &["system:serviceaccounts"]
+        )));
+        assert!(requester.matches(&user_info("system:node:worker-1", &["system:nodes"])));
+        assert!(!requester.matches(&user_info("alice", &["system:authenticated"])));
+    }
+
+    #[test]
+    fn validation() {
+        let requester = Requester {
+            usernames: Some(vec!["system:serviceaccount:kube-system:*".to_string()]),
+            reason: "cluster components".to_string(),
+            ..Default::default()
+        };
+        assert!(requester.validate().is_ok());
+
+        let requester = Requester {
+            usernames: Some(vec![]),
+            reason: "cluster components".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            requester.validate().unwrap_err(),
+            "no usernames or groups provided"
+        );
+
+        let requester = Requester {
+            groups: Some(vec!["system:nodes".to_string()]),
+            reason: "".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(requester.validate().unwrap_err(), "no reason provided");
+
+        let requester = Requester {
+            groups: Some(vec!["system:nodes".to_string()]),
+            reason: "nodes".to_string(),
+            signatures: Some(vec![]),
+            ..Default::default()
+        };
+        assert_eq!(
+            requester.validate().unwrap_err(),
+            "signatures must not be empty"
+        );
+    }
+
+    #[test]
+    fn deserialize_signatures_report_index() {
+        let result = serde_json::from_value::<Requester>(serde_json::json!({
+            "groups": ["system:nodes"],
+            "reason": "nodes",
+            "signatures": [
+                { "type": "pubKeys", "image": "*" }
+            ]
+        }));
+
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("signatures[0]: PubKeys: missing field `pubKeys`"),
+            "{error}"
+        );
+    }
+}
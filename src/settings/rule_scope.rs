@@ -0,0 +1,71 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use serde::{Deserialize, Serialize};
+
+use super::annotations::Annotations;
+use super::validity::Validity;
+
+/// Fields shared by all the signature rules and the groups, flattened into
+/// them: where and when the rule is enforced, and the annotations its
+/// signatures must have
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RuleScope {
+    /// Optional - Images matching `image` that must not be verified by the rule
+    pub(crate) exclude_images: Option<Vec<String>>,
+    /// Optional - Namespaces where the rule is enforced, supports wildcards.
+    /// When not specified, the rule is enforced in all the namespaces
+    pub(crate) namespaces: Option<Vec<String>>,
+    /// Optional - Namespaces where the rule is not enforced, supports wildcards
+    pub(crate) exclude_namespaces: Option<Vec<String>>,
+    /// Optional - Label selector the namespace must satisfy for the rule to be enforced
+    pub(crate) namespace_selector: Option<LabelSelector>,
+    /// Optional - Period of time during which the rule is enforced, outside
+    /// of it the rule is disabled
+    #[serde(flatten)]
+    pub(crate) validity: Validity,
+    /// Optional - Annotations that must have been provided by all signers
+    /// when they signed the OCI artifact. Not supported by the groups, whose
+    /// rules provide their own
+    pub(crate) annotations: Option<Annotations>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Signature;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn deserialize_flattened_scope() {
+        let signature: Signature = serde_json::from_value(serde_json::json!({
+            "image": "ghcr.io/acme/*",
+            "excludeImages": ["ghcr.io/acme/legacy-*"],
+            "namespaces": ["payments-*"],
+            "excludeNamespaces": ["payments-sandbox"],
+            "notAfter": "2030-01-01T00:00:00Z",
+            "annotations": { "env": "prod" },
+            "keyless": [{ "issuer": "issuer", "subject": "subject" }]
+        }))
+        .expect("cannot deserialize signature");
+
+        let Signature::Keyless(keyless) = &signature else {
+            panic!("not a keyless rule");
+        };
+        let scope = signature.scope();
+        assert_eq!(keyless.keyless.len(), 1);
+        assert_eq!(
+            scope.exclude_images,
+            Some(vec!["ghcr.io/acme/legacy-*".to_string()])
+        );
+        assert_eq!(scope.namespaces, Some(vec!["payments-*".to_string()]));
+        assert_eq!(
+            scope.exclude_namespaces,
+            Some(vec!["payments-sandbox".to_string()])
+        );
+        assert_eq!(
+            scope.validity.not_after,
+            Some(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap())
+        );
+        assert!(scope.annotations.is_some());
+        assert!(signature.excludes_image("ghcr.io/acme/legacy-app:v1"));
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::Validate;
+
+use super::image_reference::image_pattern_matches;
+
+#[derive(Serialize, Deserialize, Debug, Validate, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SkipImage {
+    /// Images exempted from all the signature checks. Supports wildcards
+    /// (e.g.: `docker.io/bitnami/*`)
+    #[validate(length(min = 1))]
+    pub(crate) image: String,
+    /// Justification of the exemption
+    #[validate(length(min = 1))]
+    pub(crate) reason: String,
+    /// Optional - RFC3339 date after which the image is no longer exempted
+    pub(crate) expires: Option<DateTime<Utc>>,
+}
+
+impl fmt::Display for SkipImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Skipped image {}", self.image)
+    }
+}
+
+impl SkipImage {
+    pub(crate) fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Returns true when the exemption is active and matches the image
+    pub(crate) fn exempts(&self, image: &str, now: DateTime<Utc>) -> bool {
+        !self.is_expired(now) && image_pattern_matches(&self.image, image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn skip_image(expires: Option<DateTime<Utc>>) -> SkipImage {
+        SkipImage {
+            image: "docker.io/bitnami/*".to_string(),
+            reason: "third-party images, see ticket SEC-42".to_string(),
+            expires,
+        }
+    }
+
+    #[test]
+    fn validation_requires_reason() {
+        let skip = SkipImage {
+            reason: "".to_string(),
+            ..skip_image(None)
+        };
+        assert!(skip.validate().is_err());
+        assert!(skip_image(None).validate().is_ok());
+    }
+
+    #[test]
+    fn exemption_stops_when_expired() {
+        let now = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+
+        let skip = skip_image(None);
+        assert!(skip.exempts("docker.io/bitnami/redis:7", now));
+        assert!(!skip.exempts("docker.io/library/redis:7", now));
+
+        let skip = skip_image(Some(Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap()));
+        assert!(!skip.is_expired(now));
+        assert!(skip.exempts("docker.io/bitnami/redis:7", now));
+
+        let skip = skip_image(Some(Utc.with_ymd_and_hms(2025, 5, 1, 0, 0, 0).unwrap()));
+        assert!(skip.is_expired(now));
+        assert!(!skip.exempts("docker.io/bitnami/redis:7", now));
+    }
+
+    #[test]
+    fn exemption_matches_short_names() {
+        let now = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        let skip = SkipImage {
+            image: "docker.io/library/redis:*".to_string(),
+            ..skip_image(None)
+        };
+        assert!(skip.exempts("redis", now));
+        assert!(skip.exempts("redis:7", now));
+        assert!(skip.exempts("docker.io/library/redis:7", now));
+        assert!(!skip.exempts("bitnami/redis:7", now));
+    }
+
+    #[test]
+    fn deserialize_rfc3339_expiration() {
+        let skip: SkipImage = serde_json::from_value(serde_json::json!({
+            "image": "docker.io/bitnami/*",
+            "reason": "third-party images",
+            "expires": "2025-07-01T00:00:00Z"
+        }))
+        .expect("cannot deserialize skipped image");
+        assert_eq!(
+            skip.expires,
+            Some(Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap())
+        );
+    }
+}
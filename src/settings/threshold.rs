@@ -0,0 +1,98 @@
+use validator::ValidationError;
+
+/// Returns the number of signers that must be found for the image to be
+/// trusted. `None` means all the signers are required.
+pub(crate) fn required_signers(threshold: Option<usize>, any_of_signers: bool) -> Option<usize> {
+    if any_of_signers {
+        Some(1)
+    } else {
+        threshold
+    }
+}
+
+pub(crate) fn validate_threshold(
+    threshold: Option<usize>,
+    any_of_signers: bool,
+    signers: usize,
+) -> Result<(), String> {
+    if any_of_signers && threshold.is_some() {
+        return Err("threshold and anyOfSigners cannot be set at the same time".to_string());
+    }
+
+    match threshold {
+        Some(0) => Err("threshold must be greater than 0".to_string()),
+        Some(threshold) if threshold > signers => Err(format!(
+            "threshold {threshold} is greater than the number of signers ({signers})"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validates that no signer is listed twice, as each copy would count toward
+/// the threshold. The signers are given with their index in `field`.
+pub(crate) fn validate_distinct_signers<T: PartialEq>(
+    field: &str,
+    signers: impl IntoIterator<Item = (usize, T)>,
+) -> Result<(), String> {
+    let mut seen: Vec<(usize, T)> = vec![];
+    for (index, signer) in signers {
+        if let Some((first, _)) = seen.iter().find(|(_, other)| *other == signer) {
+            return Err(format!(
+                "{field}[{index}] is the same signer as {field}[{first}]"
+            ));
+        }
+        seen.push((index, signer));
+    }
+    Ok(())
+}
+
+pub(crate) fn threshold_validation_error(message: String) -> ValidationError {
+    ValidationError::new("threshold").with_message(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, false, None)]
+    #[case(Some(2), false, Some(2))]
+    #[case(None, true, Some(1))]
+    fn required_signers_resolution(
+        #[case] threshold: Option<usize>,
+        #[case] any_of_signers: bool,
+        #[case] expected: Option<usize>,
+    ) {
+        assert_eq!(required_signers(threshold, any_of_signers), expected);
+    }
+
+    #[rstest]
+    #[case(None, false, 3, true)]
+    #[case(Some(2), false, 3, true)]
+    #[case(Some(3), false, 3, true)]
+    #[case(None, true, 3, true)]
+    #[case(Some(0), false, 3, false)]
+    #[case(Some(4), false, 3, false)]
+    #[case(Some(1), true, 3, false)]
+    fn threshold_validation(
+        #[case] threshold: Option<usize>,
+        #[case] any_of_signers: bool,
+        #[case] signers: usize,
+        #[case] is_valid: bool,
+    ) {
+        assert_eq!(
+            validate_threshold(threshold, any_of_signers, signers).is_ok(),
+            is_valid
+        );
+    }
+
+    #[test]
+    fn distinct_signers_validation() {
+        assert!(validate_distinct_signers("pubKeys", [(0, "a"), (1, "b")]).is_ok());
+        assert_eq!(
+            validate_distinct_signers("pubKeys", [(0, "a"), (1, "b"), (2, "a")]).unwrap_err(),
+            "pubKeys[2] is the same signer as pubKeys[0]"
+        );
+    }
+}
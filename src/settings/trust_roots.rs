@@ -0,0 +1,432 @@
+use chrono::Utc;
+use kubewarden::host_capabilities::verification::KeylessInfo;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use super::crl::validate_crls;
+use super::pem_material::{normalize_pem_materials, validate_pem_materials, PemMaterial};
+use super::public_key::validate_public_keys;
+
+/// Named set of trusted material, referenced by the `trustRoots` field of the
+/// `pubKeys`, `certificates` and `keyless` rules
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrustRoot {
+    /// Optional - PEM encoded public keys, optionally time bound
+    pub(crate) pub_keys: Option<Vec<PemMaterial>>,
+    /// Optional - PEM encoded certificates, optionally time bound
+    pub(crate) certificates: Option<Vec<PemMaterial>>,
+    /// Optional - PEM encoded certificates used to verify `certificates`
+    pub(crate) certificate_chain: Option<Vec<PemMaterial>>,
+    /// Optional - CRLs used to check `certificates`
+    pub(crate) crls: Option<Vec<String>>,
+    /// Optional - Keyless identities
+    pub(crate) keyless: Option<Vec<KeylessInfo>>,
+}
+
+/// Field of the signature rules that holds each kind of material
+const PUB_KEYS: &str = "pubKeys";
+const CERTIFICATES: &str = "certificates";
+const CERTIFICATE_CHAIN: &str = "certificateChain";
+const CRLS: &str = "crls";
+const KEYLESS: &str = "keyless";
+const KEYLESS_PREFIX: &str = "keylessPrefix";
+const KEYLESS_MATCH: &str = "keylessMatch";
+const GITHUB_ACTIONS: &str = "githubActions";
+const GITLAB_CI: &str = "gitlabCi";
+const LEAF_CERTIFICATE: &str = "leafCertificate";
+
+/// Fields that identify the kind of a signature rule
+const RULE_KINDS: [&str; 8] = [
+    PUB_KEYS,
+    KEYLESS,
+    KEYLESS_PREFIX,
+    KEYLESS_MATCH,
+    GITHUB_ACTIONS,
+    GITLAB_CI,
+    CERTIFICATES,
+    LEAF_CERTIFICATE,
+];
+
+/// Fields of the signature groups, which hold the list of their rules
+const GROUP_RULES: [&str; 2] = ["allOf", "anyOf"];
+
+impl TrustRoot {
+    /// Returns the field of the signature rules filled by the trust root
+    fn kind(&self) -> Result<&'static str, String> {
+        let kinds: Vec<&'static str> = [
+            (PUB_KEYS, self.pub_keys.is_some()),
+            (CERTIFICATES, self.certificates.is_some()),
+            (KEYLESS, self.keyless.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(kind, is_set)| is_set.then_some(kind))
+        .collect();
+
+        match kinds.as_slice() {
+            [kind] => Ok(kind),
+            _ => {
+                Err("exactly one of pubKeys, certificates or keyless must be provided".to_string())
+            }
+        }
+    }
+
+    /// Material added to the rules referencing the trust root, by field
+    fn material(&self) -> Vec<(&'static str, Vec<Value>)> {
+        let to_values = |values: &[PemMaterial]| {
+            values
+                .iter()
+                .map(|value| serde_json::to_value(value).unwrap_or_default())
+                .collect()
+        };
+
+        let mut material = vec![];
+        if let Some(pub_keys) = &self.pub_keys {
+            material.push((PUB_KEYS, to_values(pub_keys)));
+        }
+        if let Some(certificates) = &self.certificates {
+            material.push((CERTIFICATES, to_values(certificates)));
+        }
+        if let Some(chain) = &self.certificate_chain {
+            material.push((CERTIFICATE_CHAIN, to_values(chain)));
+        }
+        if let Some(crls) = &self.crls {
+            material.push((CRLS, crls.iter().cloned().map(Value::String).collect()));
+        }
+        if let Some(keyless) = &self.keyless {
+            material.push((
+                KEYLESS,
+                keyless
+                    .iter()
+                    .map(|k| serde_json::json!({ "issuer": k.issuer, "subject": k.subject }))
+                    .collect(),
+            ));
+        }
+        material
+    }
+
+    /// Splits the PEM bundles and encodes as PEM the base64 encoded DER
+    pub(crate) fn normalize_pem_materials(&mut self) {
+        for materials in [
+            &mut self.pub_keys,
+            &mut self.certificates,
+            &mut self.certificate_chain,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            normalize_pem_materials(materials);
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let kind = self.kind()?;
+
+        if self.certificate_chain.is_some() && kind != CERTIFICATES {
+            return Err("certificateChain can be provided only with certificates".to_string());
+        }
+        if self.crls.is_some() && kind != CERTIFICATES {
+            return Err("crls can be provided only with certificates".to_string());
+        }
+
+        let is_empty = match kind {
+            PUB_KEYS => self.pub_keys.as_ref().is_some_and(Vec::is_empty),
+            CERTIFICATES => self.certificates.as_ref().is_some_and(Vec::is_empty),
+            _ => self.keyless.as_ref().is_some_and(Vec::is_empty),
+        };
+        if is_empty {
+            return Err(format!("no {kind} provided"));
+        }
+
+        if let Some(pub_keys) = &self.pub_keys {
+            validate_public_keys(pub_keys).map_err(|e| e.to_string())?;
+        }
+        for pems in [&self.certificates, &self.certificate_chain]
+            .into_iter()
+            .flatten()
+        {
+            validate_pem_materials(pems).map_err(|e| e.code.to_string())?;
+        }
+        // the signatures of the CRLs are verified, and the outdated CRLs are
+        // reported, with the chains of the rules using the trust root
+        if let Some(crls) = &self.crls {
+            validate_crls(crls, None, Utc::now())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the material of the trust roots to the signature rules referencing
+/// them, including the rules of the groups and of the requesters. This is done
+/// on the raw settings, before the rules are deserialized, so that the kind
+/// of the rules that only reference trust roots can be inferred.
+pub(crate) fn resolve_trust_roots(settings: &mut Value) -> Result<(), String> {
+    let trust_roots: BTreeMap<String, TrustRoot> = match settings.get("trustRoots") {
+        Some(trust_roots) => {
+            serde_json::from_value(trust_roots.clone()).map_err(|e| format!("trustRoots: {e}"))?
+        }
+        None => BTreeMap::new(),
+    };
+
+    if let Some(signatures) = settings.get_mut("signatures") {
+        resolve_signatures(signatures, &trust_roots, "signatures")?;
+    }
+
+    if let Some(Value::Array(requesters)) = settings.get_mut("requesters") {
+        for (index, requester) in requesters.iter_mut().enumerate() {
+            if let Some(signatures) = requester.get_mut("signatures") {
+                resolve_signatures(
+                    signatures,
+                    &trust_roots,
+                    &format!("requesters[{index}].signatures"),
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve_signatures(
+    signatures: &mut Value,
+    trust_roots: &BTreeMap<String, TrustRoot>,
+    path: &str,
+) -> Result<(), String> {
+    let Value::Array(signatures) = signatures else {
+        return Ok(());
+    };
+
+    for (index, signature) in signatures.iter_mut().enumerate() {
+        let path = format!("{path}[{index}]");
+        let Value::Object(signature) = signature else {
+            continue;
+        };
+
+        for group_rule in GROUP_RULES {
+            if let Some(rules) = signature.get_mut(group_rule) {
+                resolve_signatures(rules, trust_roots, &format!("{path}.{group_rule}"))?;
+            }
+        }
+
+        resolve_signature(signature, trust_roots).map_err(|e| format!("{path}: {e}"))?;
+    }
+
+    Ok(())
+}
+
+fn resolve_signature(
+    signature: &mut Map<String, Value>,
+    trust_roots: &BTreeMap<String, TrustRoot>,
+) -> Result<(), String> {
+    let Some(references) = signature.remove("trustRoots") else {
+        return Ok(());
+    };
+    let references: Vec<String> =
+        serde_json::from_value(references).map_err(|e| format!("trustRoots: {e}"))?;
+
+    for name in references {
+        let trust_root = trust_roots
+            .get(&name)
+            .ok_or_else(|| format!("unknown trust root {name}"))?;
+        let kind = trust_root
+            .kind()
+            .map_err(|e| format!("trust root {name}: {e}"))?;
+
+        // the fields of the groups are only rule kinds when they are lists of
+        // rules, which is not the case of the flags of the other rules
+        if let Some(other_kind) = RULE_KINDS
+            .iter()
+            .filter(|k| **k != kind && signature.contains_key(**k))
+            .chain(
+                GROUP_RULES
+                    .iter()
+                    .filter(|k| signature.get(**k).is_some_and(Value::is_array)),
+            )
+            .next()
+        {
+            return Err(format!(
+                "trust root {name} provides {kind}, it cannot be used by a rule with {other_kind}"
+            ));
+        }
+        if let Some(signature_type) = signature.get("type").and_then(Value::as_str) {
+            let type_kind = match signature_type {
+                "certificate" => CERTIFICATES,
+                signature_type => signature_type,
+            };
+            if type_kind != kind {
+                return Err(format!(
+                    "trust root {name} provides {kind}, it cannot be used by a rule of type {signature_type}"
+                ));
+            }
+        }
+
+        for (field, values) in trust_root.material() {
+            let entry = signature
+                .entry(field)
+                .or_insert_with(|| Value::Array(vec![]));
+            match entry {
+                Value::Array(entry) => entry.extend(values),
+                _ => return Err(format!("{field} must be a list")),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings_with_trust_roots(signatures: Value) -> Value {
+        json!({
+            "trustRoots": {
+                "maintainers": { "pubKeys": ["key1", "key2"] },
+                "corporate-pki": {
+                    "certificates": ["cert"],
+                    "certificateChain": ["intermediate", "root"]
+                },
+                "release-workflow": {
+                    "keyless": [{ "issuer": "issuer", "subject": "subject" }]
+                }
+            },
+            "signatures": signatures
+        })
+    }
+
+    #[test]
+    fn resolve_adds_material_to_rules() {
+        let mut settings = settings_with_trust_roots(json!([
+            { "image": "a", "trustRoots": ["maintainers"], "threshold": 2 },
+            { "image": "b", "pubKeys": ["key3"], "trustRoots": ["maintainers"] },
+            {
+                "image": "c",
+                "anyOf": [
+                    { "trustRoots": ["corporate-pki"] },
+                    { "trustRoots": ["release-workflow"] }
+                ]
+            }
+        ]));
+
+        resolve_trust_roots(&mut settings).expect("cannot resolve trust roots");
+
+        let signatures = &settings["signatures"];
+        assert_eq!(signatures[0]["pubKeys"], json!(["key1", "key2"]));
+        assert!(signatures[0].get("trustRoots").is_none());
+        assert_eq!(signatures[1]["pubKeys"], json!(["key3", "key1", "key2"]));
+        assert_eq!(signatures[2]["anyOf"][0]["certificates"], json!(["cert"]));
+        assert_eq!(
+            signatures[2]["anyOf"][0]["certificateChain"],
+            json!(["intermediate", "root"])
+        );
+        assert_eq!(
+            signatures[2]["anyOf"][1]["keyless"],
+            json!([{ "issuer": "issuer", "subject": "subject" }])
+        );
+    }
+
+    #[test]
+    fn resolve_rules_with_signer_flags() {
+        let mut settings = settings_with_trust_roots(json!([
+            { "image": "a", "trustRoots": ["maintainers"], "anyOfSigners": true },
+            { "image": "b", "trustRoots": ["maintainers"], "anyOf": true }
+        ]));
+
+        resolve_trust_roots(&mut settings).expect("cannot resolve trust roots");
+        assert_eq!(
+            settings["signatures"][0]["pubKeys"],
+            json!(["key1", "key2"])
+        );
+        assert_eq!(
+            settings["signatures"][1]["pubKeys"],
+            json!(["key1", "key2"])
+        );
+
+        let mut settings = settings_with_trust_roots(json!([
+            { "image": "a", "trustRoots": ["maintainers"], "anyOf": [] }
+        ]));
+        assert_eq!(
+            resolve_trust_roots(&mut settings).unwrap_err(),
+            "signatures[0]: trust root maintainers provides pubKeys, it cannot be used by a rule with anyOf"
+        );
+    }
+
+    #[test]
+    fn resolve_reports_invalid_references() {
+        let mut settings = settings_with_trust_roots(json!([
+            { "image": "a", "trustRoots": ["maintainers"] },
+            { "image": "b", "allOf": [{ "trustRoots": ["unknown"] }] }
+        ]));
+        assert_eq!(
+            resolve_trust_roots(&mut settings).unwrap_err(),
+            "signatures[1].allOf[0]: unknown trust root unknown"
+        );
+
+        let mut settings = settings_with_trust_roots(json!([
+            { "image": "a", "keyless": [], "trustRoots": ["maintainers"] }
+        ]));
+        assert_eq!(
+            resolve_trust_roots(&mut settings).unwrap_err(),
+            "signatures[0]: trust root maintainers provides pubKeys, it cannot be used by a rule with keyless"
+        );
+
+        let mut settings = settings_with_trust_roots(json!([
+            { "type": "keyless", "image": "a", "trustRoots": ["corporate-pki"] }
+        ]));
+        assert_eq!(
+            resolve_trust_roots(&mut settings).unwrap_err(),
+            "signatures[0]: trust root corporate-pki provides certificates, it cannot be used by a rule of type keyless"
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let trust_root = TrustRoot {
+            pub_keys: Some(vec!["key".into()]),
+            keyless: Some(vec![]),
+            ..Default::default()
+        };
+        assert_eq!(
+            trust_root.validate().unwrap_err(),
+            "exactly one of pubKeys, certificates or keyless must be provided"
+        );
+
+        let trust_root = TrustRoot {
+            pub_keys: Some(vec![]),
+            ..Default::default()
+        };
+        assert_eq!(trust_root.validate().unwrap_err(), "no pubKeys provided");
+
+        let trust_root = TrustRoot {
+            pub_keys: Some(vec!["key".into()]),
+            certificate_chain: Some(vec!["chain".into()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            trust_root.validate().unwrap_err(),
+            "certificateChain can be provided only with certificates"
+        );
+
+        let trust_root = TrustRoot {
+            pub_keys: Some(vec!["key".into()]),
+            crls: Some(vec!["crl".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            trust_root.validate().unwrap_err(),
+            "crls can be provided only with certificates"
+        );
+
+        let trust_root = TrustRoot {
+            pub_keys: Some(vec!["key".into()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            trust_root.validate().unwrap_err(),
+            "pubKeys[0]: invalid PEM: malformedframing"
+        );
+    }
+}
@@ -1,9 +1,13 @@
 use validator::ValidationError;
 
-pub(crate) fn validate_vector_of_pem_strings(data: &[String]) -> Result<(), ValidationError> {
+use super::image_reference::normalize_image_pattern;
+
+pub(crate) fn validate_vector_of_pem_strings<S: AsRef<str>>(
+    data: &[S],
+) -> Result<(), ValidationError> {
     let violations = data
         .iter()
-        .filter(|s| pem::parse(s.as_bytes()).is_err())
+        .filter(|s| pem::parse(s.as_ref().as_bytes()).is_err())
         .count();
 
     if violations == 0 {
@@ -13,9 +17,84 @@ pub(crate) fn validate_vector_of_pem_strings(data: &[String]) -> Result<(), Vali
     }
 }
 
+/// Returns true when at least one string is matched by both the wildcard
+/// patterns. `*` matches any sequence of characters and `?` a single one.
+fn wildcard_patterns_intersect(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // visited[i][j] is true when the suffixes a[i..] and b[j..] have been explored
+    let mut visited = vec![vec![false; b.len() + 1]; a.len() + 1];
+    let mut to_visit = vec![(0, 0)];
+
+    while let Some((i, j)) = to_visit.pop() {
+        if visited[i][j] {
+            continue;
+        }
+        visited[i][j] = true;
+
+        if i == a.len() && j == b.len() {
+            return true;
+        }
+
+        let a_char = a.get(i);
+        let b_char = b.get(j);
+        if a_char == Some(&'*') {
+            // the wildcard matches nothing, or consumes the next element of `b`
+            to_visit.push((i + 1, j));
+            if b_char.is_some() {
+                to_visit.push((i, j + 1));
+            }
+        }
+        if b_char == Some(&'*') {
+            to_visit.push((i, j + 1));
+            if a_char.is_some() {
+                to_visit.push((i + 1, j));
+            }
+        }
+        if let (Some(a_char), Some(b_char)) = (a_char, b_char) {
+            if *a_char != '*'
+                && *b_char != '*'
+                && (a_char == b_char || *a_char == '?' || *b_char == '?')
+            {
+                to_visit.push((i + 1, j + 1));
+            }
+        }
+    }
+
+    false
+}
+
+/// Returns true when at least one image is matched by both the image
+/// patterns, as written or once normalized
+fn image_patterns_intersect(a: &str, b: &str) -> bool {
+    wildcard_patterns_intersect(a, b)
+        || wildcard_patterns_intersect(&normalize_image_pattern(a), &normalize_image_pattern(b))
+}
+
+pub(crate) fn validate_exclude_images(
+    image: &str,
+    exclude_images: &[String],
+) -> Result<(), String> {
+    let errors: Vec<String> = exclude_images
+        .iter()
+        .filter(|exclude_image| !image_patterns_intersect(image, exclude_image))
+        .map(|exclude_image| {
+            format!("excluded image {exclude_image} can never match image {image}")
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use rstest::rstest;
 
     pub(crate) const PEM_DATA: &str = r#"-----BEGIN CERTIFICATE-----
 MIICSzCCAfCgAwIBAgIUHKusfkyBA2FHmSje5pEiQAE5L4AwCgYIKoZIzj0EAwIw
@@ -45,4 +124,50 @@ uDQIMUZKKXu/8si37BkB
         let data = vec!["foo".to_string()];
         assert!(validate_vector_of_pem_strings(&data).is_err());
     }
+
+    #[rstest]
+    #[case("ghcr.io/acme/*", "ghcr.io/acme/legacy-*", true, true)]
+    #[case("ghcr.io/acme/*", "ghcr.io/acme/legacy:1.0", true, true)]
+    #[case("ghcr.io/acme/*:v?", "*:v1", true, true)]
+    #[case("*", "anything", true, true)]
+    #[case("ghcr.io/acme/*", "ghcr.io/other/*", false, false)]
+    #[case("ghcr.io/acme/app:?", "ghcr.io/acme/app:10", false, false)]
+    #[case("ghcr.io/acme/app", "ghcr.io/acme/app:*", false, true)]
+    #[case("nginx:*", "docker.io/library/nginx:1.0", false, true)]
+    #[case("nginx:*", "docker.io/bitnami/nginx:1.0", false, false)]
+    fn wildcard_patterns_intersection(
+        #[case] a: &str,
+        #[case] b: &str,
+        #[case] expected: bool,
+        #[case] expected_normalized: bool,
+    ) {
+        assert_eq!(wildcard_patterns_intersect(a, b), expected);
+        assert_eq!(wildcard_patterns_intersect(b, a), expected);
+        assert_eq!(image_patterns_intersect(a, b), expected_normalized);
+        assert_eq!(image_patterns_intersect(b, a), expected_normalized);
+    }
+
+    #[test]
+    fn exclude_images_validation() {
+        assert!(
+            validate_exclude_images("ghcr.io/acme/*", &["ghcr.io/acme/legacy-*".to_string()])
+                .is_ok()
+        );
+        assert!(
+            validate_exclude_images("nginx:*", &["docker.io/library/nginx:1.0".to_string()])
+                .is_ok()
+        );
+
+        assert_eq!(
+            validate_exclude_images(
+                "ghcr.io/acme/*",
+                &[
+                    "ghcr.io/acme/legacy-*".to_string(),
+                    "docker.io/acme/*".to_string()
+                ]
+            )
+            .unwrap_err(),
+            "excluded image docker.io/acme/* can never match image ghcr.io/acme/*"
+        );
+    }
 }
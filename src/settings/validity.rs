@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Period of time during which a signature rule, a key or a certificate is used
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Validity {
+    /// Optional - RFC3339 date before which it is not used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) not_before: Option<DateTime<Utc>>,
+    /// Optional - RFC3339 date after which it is no longer used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) not_after: Option<DateTime<Utc>>,
+}
+
+impl Validity {
+    pub(crate) fn is_set(&self) -> bool {
+        self.not_before.is_some() || self.not_after.is_some()
+    }
+
+    pub(crate) fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.is_none_or(|not_before| not_before <= now)
+            && self.not_after.is_none_or(|not_after| now < not_after)
+    }
+
+    /// Returns true when the window is still open and closes before the horizon
+    pub(crate) fn expires_before(&self, now: DateTime<Utc>, horizon: DateTime<Utc>) -> bool {
+        self.not_after
+            .is_some_and(|not_after| now < not_after && not_after <= horizon)
+    }
+
+    pub(crate) fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        if let (Some(not_before), Some(not_after)) = (self.not_before, self.not_after) {
+            if not_before >= not_after {
+                return Err(format!(
+                    "notBefore {} is not before notAfter {}",
+                    not_before.to_rfc3339(),
+                    not_after.to_rfc3339()
+                ));
+            }
+        }
+        match self.not_after {
+            Some(not_after) if not_after <= now => Err(format!(
+                "the validity window ended on {}",
+                not_after.to_rfc3339()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rstest::rstest;
+
+    fn date(year: i32, month: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+    }
+
+    fn validity(not_before: Option<(i32, u32)>, not_after: Option<(i32, u32)>) -> Validity {
+        Validity {
+            not_before: not_before.map(|(y, m)| date(y, m)),
+            not_after: not_after.map(|(y, m)| date(y, m)),
+        }
+    }
+
+    #[rstest]
+    #[case::unbounded(None, None, true)]
+    #[case::started(Some((2025, 1)), None, true)]
+    #[case::not_started(Some((2025, 7)), None, false)]
+    #[case::ending(None, Some((2025, 7)), true)]
+    #[case::ended(None, Some((2025, 6)), false)]
+    #[case::within(Some((2025, 1)), Some((2025, 7)), true)]
+    fn window_activity(
+        #[case] not_before: Option<(i32, u32)>,
+        #[case] not_after: Option<(i32, u32)>,
+        #[case] active: bool,
+    ) {
+        assert_eq!(
+            validity(not_before, not_after).is_active(date(2025, 6)),
+            active
+        );
+    }
+
+    #[test]
+    fn window_validation() {
+        let now = date(2025, 6);
+
+        assert!(validity(Some((2025, 1)), Some((2025, 7)))
+            .validate(now)
+            .is_ok());
+        assert!(validity(Some((2026, 1)), None).validate(now).is_ok());
+        assert_eq!(
+            validity(Some((2025, 7)), Some((2025, 1)))
+                .validate(now)
+                .unwrap_err(),
+            "notBefore 2025-07-01T00:00:00+00:00 is not before notAfter 2025-01-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            validity(Some((2025, 1)), Some((2025, 3)))
+                .validate(now)
+                .unwrap_err(),
+            "the validity window ended on 2025-03-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn window_expiration() {
+        let (now, horizon) = (date(2025, 6), date(2025, 7));
+
+        assert!(validity(None, Some((2025, 7))).expires_before(now, horizon));
+        assert!(!validity(None, Some((2025, 8))).expires_before(now, horizon));
+        assert!(!validity(None, Some((2025, 5))).expires_before(now, horizon));
+        assert!(!validity(None, None).expires_before(now, horizon));
+    }
+}